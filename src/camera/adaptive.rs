@@ -0,0 +1,60 @@
+// spends samples where the image is still noisy. each pixel takes at least
+// min_samples, then keeps sampling until the standard error of its
+// luminance falls below max_relative_error of its value (e.g. 0.01 for 1%)
+// or it reaches max_samples
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveSampling {
+    pub min_samples: u32,
+    pub max_samples: u32,
+    pub max_relative_error: f64,
+}
+
+impl AdaptiveSampling {
+    // true once a pixel with the given sample count and error is done
+    pub(super) fn converged(&self, samples: u32, relative_error: f64) -> bool {
+        samples >= self.max_samples
+            || (samples >= self.min_samples && relative_error <= self.max_relative_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::film::FilmPixel;
+    use super::*;
+    use crate::color::Color;
+
+    const ADAPTIVE: AdaptiveSampling = AdaptiveSampling {
+        min_samples: 16,
+        max_samples: 1024,
+        max_relative_error: 0.02,
+    };
+
+    // the samples a grey pixel takes with the given luminances in turn
+    fn samples_taken(luminance: impl Fn(u32) -> f64) -> u32 {
+        let mut pixel = FilmPixel::new();
+        while !ADAPTIVE.converged(pixel.samples, pixel.relative_error()) {
+            let value = luminance(pixel.samples);
+            pixel.add_sample(Color::new(value, value, value), 1.0);
+        }
+        pixel.samples
+    }
+
+    #[test]
+    fn flat_pixels_stop_at_the_minimum() {
+        assert_eq!(samples_taken(|_| 0.5), 16);
+    }
+
+    #[test]
+    fn noisy_pixels_run_to_the_maximum() {
+        // a standard deviation as large as the mean needs 2500 samples
+        assert_eq!(samples_taken(|k| [0.0, 1.0][k as usize % 2]), 1024);
+    }
+
+    #[test]
+    fn dark_pixels_use_the_absolute_floor() {
+        // relative to its mean of 0.002 this noise would need 2500 samples,
+        // but against the floor of 0.01 it needs only 100
+        let samples = samples_taken(|k| [0.0, 0.004][k as usize % 2]);
+        assert!((90..=110).contains(&samples), "{}", samples);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::sampler::{Sampler, ThreadSampler};
+use crate::texture::Texture;
+use crate::vec3::{sample_unit_disk, Point, Vec3};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// the shape of the lens opening, which is what out-of-focus highlights
+// (bokeh) take the shape of
+#[derive(Clone)]
+pub enum Aperture {
+    Circle,
+    // a regular polygon from `blades` straight diaphragm blades, rotated by
+    // `rotation` degrees
+    Polygon { blades: u32, rotation: f64 },
+    // an arbitrary opening given by a grayscale mask over [0, 1]^2, where
+    // brighter texels let through more light (e.g. an ImageTexture)
+    Mask(Arc<dyn Texture>),
+}
+
+impl Aperture {
+    // a random point on the aperture, scaled to the unit disk, or None if
+    // no point was found (for masks that are mostly closed)
+    pub fn sample(&self, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        match self {
+            Aperture::Circle => Some(sample_unit_disk(sampler.get_2d())),
+            Aperture::Polygon { blades, rotation } => {
+                let blades = (*blades).max(3);
+                // pick one of the triangles fanning out from the center
+                // (they all have the same area), then a point inside it,
+                // reusing what is left of the first coordinate
+                let (u, mut b) = sampler.get_2d();
+                let k = (u * blades as f64) as u32 % blades;
+                let mut a = u * blades as f64 - k as f64;
+                let vertex = |k: u32| {
+                    let angle = rotation.to_radians() + 2.0 * PI * k as f64 / blades as f64;
+                    Vec3::new(angle.cos(), angle.sin(), 0.0)
+                };
+
+                if a + b > 1.0 {
+                    (a, b) = (1.0 - a, 1.0 - b);
+                }
+                Some(a * vertex(k) + b * vertex(k + 1))
+            }
+            Aperture::Mask(mask) => {
+                // rejection sample the mask, giving up eventually so that a
+                // closed (black) mask can't hang the render. only the first
+                // try draws from the sampler, so the lens always takes the
+                // same dimensions
+                let mut retry = ThreadSampler;
+                for attempt in 0..64 {
+                    let sampler: &mut dyn Sampler = match attempt {
+                        0 => &mut *sampler,
+                        _ => &mut retry,
+                    };
+                    let (x, y) = sampler.get_2d();
+                    let p = Vec3::new(2.0 * x - 1.0, 2.0 * y - 1.0, 0.0);
+                    let value = mask.value(x, y, p);
+                    if (value.x + value.y + value.z) / 3.0 > sampler.get_1d() {
+                        return Some(p);
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    // a point on the aperture for a pixel at `film_position` (relative to
+    // the image center, with the corners at length 1). cats_eye > 0 clips
+    // the aperture with a second, shifted circle like a lens barrel does,
+    // so that bokeh toward the edges of the frame turns into cat's eyes
+    pub fn sample_vignetted(
+        &self,
+        film_position: Point,
+        cats_eye: f64,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Vec3> {
+        if cats_eye <= 0.0 {
+            return self.sample(sampler);
+        }
+
+        // retries come from the thread's generator, as for masks
+        let mut retry = ThreadSampler;
+        for attempt in 0..64 {
+            let sampler: &mut dyn Sampler = match attempt {
+                0 => &mut *sampler,
+                _ => &mut retry,
+            };
+            let p = self.sample(sampler)?;
+            if (p + cats_eye * film_position).length() <= 1.0 {
+                return Some(p);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::sampler::IndependentSampler;
+
+    // an opening that is only the left half of the lens
+    struct LeftHalf;
+
+    impl Texture for LeftHalf {
+        fn value(&self, u: f64, _v: f64, _p: Point) -> Color {
+            let open = if u < 0.5 { 1.0 } else { 0.0 };
+            Color::new(open, open, open)
+        }
+    }
+
+    fn samples(aperture: &Aperture, film_position: Point, cats_eye: f64) -> Vec<Vec3> {
+        let mut sampler = IndependentSampler::new(11);
+        (0..2048)
+            .map(|index| {
+                sampler.start_pixel_sample((0, 0), index);
+                aperture
+                    .sample_vignetted(film_position, cats_eye, &mut sampler)
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn polygon_samples_stay_inside_the_polygon() {
+        let (blades, rotation) = (6, 15.0);
+        let aperture = Aperture::Polygon { blades, rotation };
+        let center = Point::new(0.0, 0.0, 0.0);
+
+        // the distance from the center to the middle of each edge
+        let inradius = (PI / blades as f64).cos();
+        let points = samples(&aperture, center, 0.0);
+        for p in &points {
+            for k in 0..blades {
+                let angle = rotation.to_radians() + PI * (2 * k + 1) as f64 / blades as f64;
+                let normal = Vec3::new(angle.cos(), angle.sin(), 0.0);
+                assert!(Vec3::dot(*p, normal) <= inradius + 1e-12, "{:?}", p);
+            }
+        }
+
+        // and reach out toward the corners
+        assert!(points.iter().any(|p| p.length() > 0.97));
+    }
+
+    #[test]
+    fn mask_samples_stay_inside_the_opening() {
+        let aperture = Aperture::Mask(Arc::new(LeftHalf));
+        let points = samples(&aperture, Point::new(0.0, 0.0, 0.0), 0.0);
+        assert!(points.iter().all(|p| p.x < 0.0 && p.y.abs() <= 1.0));
+        assert!(points.iter().any(|p| p.x < -0.9));
+    }
+
+    #[test]
+    fn cats_eye_only_clips_off_axis_samples() {
+        let aperture = Aperture::Circle;
+        let cats_eye = 0.5;
+
+        // on the optical axis the barrel circle lines up with the aperture
+        let center = Point::new(0.0, 0.0, 0.0);
+        let open = samples(&aperture, center, 0.0);
+        for (p, q) in open.iter().zip(samples(&aperture, center, cats_eye)) {
+            assert_eq!(*p, q);
+        }
+
+        // toward the corner the far side of the aperture is cut off
+        let corner = Point::new(1.0, 0.0, 0.0);
+        let clipped = samples(&aperture, corner, cats_eye);
+        assert!(clipped
+            .iter()
+            .all(|p| p.length() <= 1.0 && (*p + cats_eye * corner).length() <= 1.0));
+        assert!(open.iter().any(|p| (*p + cats_eye * corner).length() > 1.0));
+        assert!(clipped.iter().any(|p| p.x < -0.4));
+    }
+}
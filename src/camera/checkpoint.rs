@@ -0,0 +1,103 @@
+use super::film::FilmPixel;
+use crate::sampler::SamplerKind;
+
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 8] = b"RTCKPT3\n";
+
+// the samplers in the order of their number in a checkpoint
+const SAMPLERS: [SamplerKind; 5] = [
+    SamplerKind::Independent,
+    SamplerKind::Stratified,
+    SamplerKind::Halton,
+    SamplerKind::Sobol,
+    SamplerKind::BlueNoise,
+];
+
+// what a render has to match to continue a checkpoint, except for
+// sampler_samples, which the render takes over
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Header {
+    pub(super) size: (i32, i32),
+    pub(super) seed: u64,
+    pub(super) sampler: SamplerKind,
+    // the sample count the sampler laid the samples out for
+    pub(super) sampler_samples: u32,
+}
+
+// a render in progress: the header and the sums of every pixel. the random
+// numbers of each sample come from a stream picked by the seed, the pixel
+// and the sample index, so the sample counts are all the generator state
+// needed to carry on exactly where the render stopped
+pub(super) fn write(mut out: impl Write, header: &Header, film: &[FilmPixel]) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    out.write_all(&(header.size.0 as u32).to_le_bytes())?;
+    out.write_all(&(header.size.1 as u32).to_le_bytes())?;
+    out.write_all(&header.seed.to_le_bytes())?;
+    let sampler = SAMPLERS
+        .iter()
+        .position(|&sampler| sampler == header.sampler)
+        .unwrap() as u32;
+    out.write_all(&sampler.to_le_bytes())?;
+    out.write_all(&header.sampler_samples.to_le_bytes())?;
+    for pixel in film {
+        pixel.write(&mut out)?;
+    }
+    Ok(())
+}
+
+// the film of a checkpoint and the sample count its sampler used. the
+// checkpoint must be of the same size, seed and sampler as the render that
+// continues it
+pub(super) fn read(
+    mut input: impl Read,
+    (width, height): (i32, i32),
+    seed: u64,
+    sampler: SamplerKind,
+) -> io::Result<(Vec<FilmPixel>, u32)> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    let mut magic = [0; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a render checkpoint".to_string()));
+    }
+    let checkpoint_width = read_u32(&mut input)?;
+    let checkpoint_height = read_u32(&mut input)?;
+    if (checkpoint_width, checkpoint_height) != (width as u32, height as u32) {
+        return Err(invalid(format!(
+            "checkpoint is {}x{}, but the image is {}x{}",
+            checkpoint_width, checkpoint_height, width, height
+        )));
+    }
+    let mut checkpoint_seed = [0; 8];
+    input.read_exact(&mut checkpoint_seed)?;
+    let checkpoint_seed = u64::from_le_bytes(checkpoint_seed);
+    if checkpoint_seed != seed {
+        return Err(invalid(format!(
+            "checkpoint was rendered with seed {}, not {}",
+            checkpoint_seed, seed
+        )));
+    }
+    let checkpoint_sampler = SAMPLERS
+        .get(read_u32(&mut input)? as usize)
+        .ok_or_else(|| invalid("checkpoint has an unknown sampler".to_string()))?;
+    if *checkpoint_sampler != sampler {
+        return Err(invalid(format!(
+            "checkpoint was rendered with the {:?} sampler, not {:?}",
+            checkpoint_sampler, sampler
+        )));
+    }
+    let sampler_samples = read_u32(&mut input)?;
+
+    let film = (0..width as usize * height as usize)
+        .map(|_| FilmPixel::read(&mut input))
+        .collect::<io::Result<_>>()?;
+    Ok((film, sampler_samples))
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
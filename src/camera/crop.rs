@@ -0,0 +1,44 @@
+use super::Camera;
+
+use std::ops::Range;
+
+// renders only a rectangle of the output image, in pixels from its top left
+// corner. the pixels inside are sampled exactly as in the full render, so
+// crops rendered separately can be pasted together into it
+#[derive(Debug, Clone, Copy)]
+pub struct Crop {
+    pub left: usize,
+    pub top: usize,
+    pub width: usize,
+    pub height: usize,
+    // write the full size image, black outside the crop, instead of only
+    // the cropped part
+    pub full_frame: bool,
+}
+
+impl Camera {
+    // the rows and columns of the output image to render, cut to fit in it
+    pub(super) fn region(&self) -> (Range<usize>, Range<usize>) {
+        let (output_width, output_height) = self.output_size();
+        let (width, height) = (output_width as usize, output_height as usize);
+        match self.crop {
+            Some(crop) => (
+                crop.top.min(height)..(crop.top + crop.height).min(height),
+                crop.left.min(width)..(crop.left + crop.width).min(width),
+            ),
+            None => (0..height, 0..width),
+        }
+    }
+
+    // the pixels written to the image, which are the whole output unless it
+    // is cropped
+    pub(super) fn image_region(&self) -> (Range<usize>, Range<usize>) {
+        match self.crop {
+            Some(crop) if !crop.full_frame => self.region(),
+            _ => {
+                let (output_width, output_height) = self.output_size();
+                (0..output_height as usize, 0..output_width as usize)
+            }
+        }
+    }
+}
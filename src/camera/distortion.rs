@@ -0,0 +1,79 @@
+// Brown-Conrady lens distortion, using the same coefficients and image
+// coordinates as OpenCV (x to the right, y down, both divided by the focal
+// length) so that the values from a camera calibration can be used as is
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Distortion {
+    // radial coefficients. negative k1 gives barrel distortion and
+    // positive k1 pincushion distortion
+    pub k1: f64,
+    pub k2: f64,
+    pub k3: f64,
+    // tangential coefficients, from lens elements that aren't quite
+    // centered on the optical axis
+    pub p1: f64,
+    pub p2: f64,
+}
+
+impl Distortion {
+    // where the undistorted point (x, y) lands in the distorted image
+    pub fn distort(&self, x: f64, y: f64) -> (f64, f64) {
+        let r2 = x * x + y * y;
+        let radial = 1.0 + r2 * (self.k1 + r2 * (self.k2 + r2 * self.k3));
+        (
+            x * radial + 2.0 * self.p1 * x * y + self.p2 * (r2 + 2.0 * x * x),
+            y * radial + self.p1 * (r2 + 2.0 * y * y) + 2.0 * self.p2 * x * y,
+        )
+    }
+
+    // the undistorted point that lands on (x, y) in the distorted image.
+    // there is no closed form, so this refines the guess with Newton's
+    // method, which converges in a few steps for realistic lenses
+    pub fn undistort(&self, x: f64, y: f64) -> (f64, f64) {
+        let (mut ux, mut uy) = (x, y);
+        for _ in 0..20 {
+            let (dx, dy) = self.distort(ux, uy);
+            let (ex, ey) = (dx - x, dy - y);
+            if ex * ex + ey * ey < 1e-24 {
+                break;
+            }
+
+            // jacobian of distort at (ux, uy)
+            let r2 = ux * ux + uy * uy;
+            let radial = 1.0 + r2 * (self.k1 + r2 * (self.k2 + r2 * self.k3));
+            let dradial = 2.0 * (self.k1 + r2 * (2.0 * self.k2 + 3.0 * r2 * self.k3));
+            let a = radial + ux * ux * dradial + 2.0 * self.p1 * uy + 6.0 * self.p2 * ux;
+            let b = ux * uy * dradial + 2.0 * self.p1 * ux + 2.0 * self.p2 * uy;
+            let c = ux * uy * dradial + 2.0 * self.p1 * ux + 2.0 * self.p2 * uy;
+            let d = radial + uy * uy * dradial + 6.0 * self.p1 * uy + 2.0 * self.p2 * ux;
+
+            let det = a * d - b * c;
+            if det.abs() < 1e-12 {
+                break;
+            }
+            ux -= (d * ex - b * ey) / det;
+            uy -= (a * ey - c * ex) / det;
+        }
+        (ux, uy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undistort_inverts_distort() {
+        let distortion = Distortion {
+            k1: -0.3,
+            k2: 0.1,
+            k3: -0.01,
+            p1: 0.002,
+            p2: -0.001,
+        };
+        for &(x, y) in &[(0.0, 0.0), (0.3, -0.2), (-0.5, 0.4), (0.7, 0.1)] {
+            let (dx, dy) = distortion.distort(x, y);
+            let (ux, uy) = distortion.undistort(dx, dy);
+            assert!((ux - x).abs() < 1e-9 && (uy - y).abs() < 1e-9);
+        }
+    }
+}
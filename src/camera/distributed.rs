@@ -0,0 +1,434 @@
+use super::film::FilmPixel;
+use super::progress::Progress;
+use super::tiles::{self, Tile};
+use super::Camera;
+use crate::hittable::Hittable;
+use crate::stats::{self, Counters, RenderStats};
+
+use rayon::prelude::*;
+use std::collections::VecDeque;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// a worker that takes longer than this to set up or return a tile is taken
+// for dead
+const WORKER_TIMEOUT: Duration = Duration::from_secs(600);
+
+// the first thing both sides send, so that a coordinator and worker built
+// from different versions of the protocol give up at once
+pub(super) const PROTOCOL_VERSION: u32 = 2;
+
+// messages from the coordinator after the job
+pub(super) const TILE: u8 = 1;
+const FINISHED: u8 = 0;
+const REJECTED: u8 = 2;
+
+// pixels along the diagonal of the image whose first samples make up the
+// fingerprint of a scene and camera
+const FINGERPRINT_PIXELS: i32 = 16;
+
+// rendering split across worker processes. the coordinator listens for
+// workers, sends each the job (the command line that sets up the scene, so
+// the worker builds the same scene and camera) and then hands out tiles
+// one at a time. a worker answers the job with the fingerprint of what it
+// set up, and is turned away if that isn't the coordinator's. every pixel
+// is sampled the same way wherever it is rendered, so the merged image is
+// the same as a local render. the tile of a worker that disconnects or
+// misses its deadline goes back to the others
+
+// the state shared by the threads talking to the workers
+struct Tiles {
+    pending: VecDeque<Tile>,
+    remaining: usize,
+    // the work the workers did on the tiles they returned
+    counters: Counters,
+}
+
+impl Camera {
+    // render the image on the workers that connect to the listener and
+    // write it to stdout
+    pub fn render_distributed(
+        &self,
+        world: &dyn Hittable,
+        listener: TcpListener,
+        job: &[String],
+    ) -> io::Result<RenderStats> {
+        let start = Instant::now();
+        let (film, counters) = self.coordinate(world, listener, job)?;
+        let mut stats = RenderStats {
+            counters,
+            ..Default::default()
+        };
+        stats.phases.push(("render", start.elapsed()));
+
+        let start = Instant::now();
+        self.write_image(&mut io::stdout().lock(), &film)?;
+        stats.phases.push(("output", start.elapsed()));
+        Ok(stats)
+    }
+
+    pub(super) fn coordinate(
+        &self,
+        world: &dyn Hittable,
+        listener: TcpListener,
+        job: &[String],
+    ) -> io::Result<(Vec<FilmPixel>, Counters)> {
+        let fingerprint = self.fingerprint(world);
+        let (rows, columns) = self.region();
+        let tiles = tiles::tiles(
+            rows.clone(),
+            columns.clone(),
+            self.tile_size,
+            self.tile_order,
+        );
+        let state = Mutex::new(Tiles {
+            remaining: tiles.len(),
+            pending: tiles.into(),
+            counters: Counters::default(),
+        });
+        let changed = Condvar::new();
+        let film = Mutex::new(self.new_film());
+        let (rows, columns) = self.region();
+        let pixels = (rows.len() * columns.len()) as u64;
+        let progress = Progress::new("Rendering".to_string(), pixels, self.quiet);
+
+        // poll for workers, so that accepting stops once the image is done
+        listener.set_nonblocking(true)?;
+        progress.message(&format!(
+            "Waiting for workers on {}",
+            listener.local_addr()?
+        ));
+        thread::scope(|scope| {
+            while state.lock().unwrap().remaining > 0 {
+                match listener.accept() {
+                    Ok((stream, address)) => {
+                        progress.message(&format!("Worker {} connected", address));
+                        let (state, changed, film) = (&state, &changed, &film);
+                        let progress = &progress;
+                        scope.spawn(move || {
+                            let job = (job, fingerprint);
+                            self.serve_worker(stream, job, state, changed, film, progress);
+                        });
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(Duration::from_millis(50));
+                    }
+                    Err(err) => progress.warning(&format!("Failed to accept a worker: {}", err)),
+                }
+            }
+            // wake the workers waiting for tiles, so they are told to stop
+            changed.notify_all();
+        });
+        progress.finish();
+        let counters = state.into_inner().unwrap().counters;
+        Ok((film.into_inner().unwrap(), counters))
+    }
+
+    // hand tiles to one worker until there are none left, merging the
+    // pixels it sends back into the film. the job comes with the
+    // fingerprint the worker has to match
+    fn serve_worker(
+        &self,
+        stream: TcpStream,
+        (job, fingerprint): (&[String], u64),
+        state: &Mutex<Tiles>,
+        changed: &Condvar,
+        film: &Mutex<Vec<FilmPixel>>,
+        progress: &Progress,
+    ) {
+        let address = stream
+            .peer_addr()
+            .map(|address| address.to_string())
+            .unwrap_or_default();
+        let connection = stream.set_nonblocking(false).and_then(|()| {
+            let input = Deadline {
+                stream: stream.try_clone()?,
+                deadline: Instant::now() + WORKER_TIMEOUT,
+            };
+            Ok((BufReader::new(input), BufWriter::new(stream)))
+        });
+        let (mut input, mut output) = match connection {
+            Ok(connection) => connection,
+            Err(err) => return progress.warning(&format!("Worker {} failed: {}", address, err)),
+        };
+        if let Err(err) = send_job(&mut input, &mut output, job, fingerprint) {
+            return progress.warning(&format!("Worker {} failed: {}", address, err));
+        }
+
+        loop {
+            let tile = {
+                let mut state = state.lock().unwrap();
+                loop {
+                    if let Some(tile) = state.pending.pop_front() {
+                        break Some(tile);
+                    }
+                    // a tile of a worker still rendering may come back
+                    if state.remaining == 0 {
+                        break None;
+                    }
+                    state = changed.wait(state).unwrap();
+                }
+            };
+            let Some(tile) = tile else {
+                let _ = output.write_all(&[FINISHED]).and_then(|()| output.flush());
+                return;
+            };
+
+            input.get_mut().deadline = Instant::now() + WORKER_TIMEOUT;
+            match request_tile(&mut input, &mut output, &tile) {
+                Ok((tile_film, counters)) => {
+                    let width = self.output_size().0 as usize;
+                    let mut film = film.lock().unwrap();
+                    for (i, row) in tile.rows.clone().zip(tile_film.chunks(tile.columns.len())) {
+                        film[i * width..][tile.columns.clone()].copy_from_slice(row);
+                    }
+                    drop(film);
+                    progress.advance(tile_film.len() as u64, counters.samples);
+                    let mut state = state.lock().unwrap();
+                    state.remaining -= 1;
+                    state.counters += counters;
+                    changed.notify_all();
+                }
+                Err(err) => {
+                    progress.warning(&format!(
+                        "Worker {} failed: {}, handing its tile to another",
+                        address, err
+                    ));
+                    state.lock().unwrap().pending.push_front(tile);
+                    changed.notify_all();
+                    return;
+                }
+            }
+        }
+    }
+
+    // a hash of the first sample of pixels along the diagonal, which is
+    // the same for two processes only if they set up the same scene and
+    // camera. the coordinator checks a worker's against its own
+    pub(super) fn fingerprint(&self, world: &dyn Hittable) -> u64 {
+        let (width, height) = self.output_size();
+        let mut sampler = self.sampler.create(self.max_samples(), self.seed);
+        let mut hash = Fnv::new();
+        for value in [
+            width as u64,
+            height as u64,
+            self.seed,
+            self.max_samples() as u64,
+        ] {
+            hash.add(value);
+        }
+        for k in 0..FINGERPRINT_PIXELS {
+            let pixel = (
+                k * height / FINGERPRINT_PIXELS,
+                k * width / FINGERPRINT_PIXELS,
+            );
+            let (color, weight) = self.sample_pixel(world, pixel, 0, sampler.as_mut());
+            for value in [color.x, color.y, color.z, weight] {
+                hash.add(value.to_bits());
+            }
+        }
+        // the samples aren't part of the render
+        stats::take_counters();
+        hash.0
+    }
+}
+
+// 64-bit FNV-1a, which unlike the standard hasher is the same in every
+// build
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    fn add(&mut self, value: u64) {
+        for byte in value.to_le_bytes() {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+// the coordinator's side of a worker connection, which fails reads that
+// go past the deadline of the tile the worker is rendering rather than
+// timing out each read on its own
+struct Deadline {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for Deadline {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "worker missed its deadline",
+            ));
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+// connect to the coordinator, set up the scene of its job and render tiles
+// for it until the image is done
+pub fn work<W: Hittable>(
+    coordinator: &str,
+    setup: impl FnOnce(&[String]) -> (W, Camera),
+) -> io::Result<()> {
+    let stream = TcpStream::connect(coordinator)?;
+    let mut input = BufReader::new(stream.try_clone()?);
+    let mut output = BufWriter::new(stream);
+
+    let version = read_u32(&mut input)?;
+    output.write_all(&PROTOCOL_VERSION.to_le_bytes())?;
+    output.flush()?;
+    if version != PROTOCOL_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "the coordinator speaks protocol version {}, not {}",
+                version, PROTOCOL_VERSION
+            ),
+        ));
+    }
+    let (world, camera) = setup(&read_job(&mut input)?);
+    output.write_all(&camera.fingerprint(&world).to_le_bytes())?;
+    output.flush()?;
+
+    loop {
+        match read_u8(&mut input)? {
+            TILE => {}
+            FINISHED => return Ok(()),
+            REJECTED => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the coordinator set up a different scene or camera",
+                ))
+            }
+            message => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown message {}", message),
+                ))
+            }
+        }
+        let tile = Tile {
+            rows: read_u32(&mut input)? as usize..read_u32(&mut input)? as usize,
+            columns: read_u32(&mut input)? as usize..read_u32(&mut input)? as usize,
+        };
+
+        // the rows of the tile in parallel
+        let mut tile_film = vec![FilmPixel::new(); tile.rows.len() * tile.columns.len()];
+        let counters = tile_film
+            .par_chunks_mut(tile.columns.len().max(1))
+            .zip(tile.rows.clone().into_par_iter())
+            .map(|(row, i)| {
+                let row_tile = Tile {
+                    rows: i..i + 1,
+                    columns: tile.columns.clone(),
+                };
+                camera.render_tile(&world, &row_tile, row, u32::MAX, camera.max_samples());
+                stats::take_counters()
+            })
+            .reduce(Counters::default, |mut total, counters| {
+                total += counters;
+                total
+            });
+
+        for pixel in &tile_film {
+            pixel.write(&mut output)?;
+        }
+        counters.write(&mut output)?;
+        output.flush()?;
+    }
+}
+
+// send the job to a worker and check that it speaks the same protocol and
+// set up the same scene and camera from it
+fn send_job(
+    input: &mut impl Read,
+    output: &mut impl Write,
+    job: &[String],
+    fingerprint: u64,
+) -> io::Result<()> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    output.write_all(&PROTOCOL_VERSION.to_le_bytes())?;
+    write_job(output, job)?;
+    let version = read_u32(input)?;
+    if version != PROTOCOL_VERSION {
+        return Err(invalid(format!(
+            "it speaks protocol version {}, not {}",
+            version, PROTOCOL_VERSION
+        )));
+    }
+    if read_u64(input)? != fingerprint {
+        output.write_all(&[REJECTED])?;
+        output.flush()?;
+        return Err(invalid("it set up a different scene or camera".to_string()));
+    }
+    Ok(())
+}
+
+fn request_tile(
+    input: &mut impl Read,
+    output: &mut impl Write,
+    tile: &Tile,
+) -> io::Result<(Vec<FilmPixel>, Counters)> {
+    output.write_all(&[TILE])?;
+    for bound in [
+        tile.rows.start,
+        tile.rows.end,
+        tile.columns.start,
+        tile.columns.end,
+    ] {
+        output.write_all(&(bound as u32).to_le_bytes())?;
+    }
+    output.flush()?;
+    let tile_film = (0..tile.rows.len() * tile.columns.len())
+        .map(|_| FilmPixel::read(&mut *input))
+        .collect::<io::Result<_>>()?;
+    Ok((tile_film, Counters::read(input)?))
+}
+
+fn write_job(output: &mut impl Write, job: &[String]) -> io::Result<()> {
+    output.write_all(&(job.len() as u32).to_le_bytes())?;
+    for arg in job {
+        output.write_all(&(arg.len() as u32).to_le_bytes())?;
+        output.write_all(arg.as_bytes())?;
+    }
+    output.flush()
+}
+
+pub(super) fn read_job(input: &mut impl Read) -> io::Result<Vec<String>> {
+    (0..read_u32(input)?)
+        .map(|_| {
+            let mut arg = vec![0; read_u32(input)? as usize];
+            input.read_exact(&mut arg)?;
+            String::from_utf8(arg).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        })
+        .collect()
+}
+
+pub(super) fn read_u8(input: &mut impl Read) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+pub(super) fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
@@ -0,0 +1,83 @@
+// physical camera settings which together set how bright the image is.
+// with these, scene radiance is taken to be in cd/m^2 (nits) and world
+// units in meters, so renders can be matched against real photographs
+#[derive(Debug, Clone, Copy)]
+pub struct Exposure {
+    // focal length divided by the aperture diameter
+    pub f_number: f64,
+    // shutter time in seconds
+    pub shutter_speed: f64,
+    pub iso: f64,
+}
+
+impl Exposure {
+    // height of a 35mm full frame sensor, which relates the field of view
+    // to a focal length
+    const SENSOR_HEIGHT: f64 = 0.024;
+
+    // lens transmission and vignetting factor used by ISO 12232
+    const LENS_FACTOR: f64 = 0.65;
+
+    // multiplier from scene luminance to pixel values. this is the
+    // saturation based convention, where a luminance of
+    // 78 / (iso * q) * N^2 / t just saturates the sensor (maps to 1.0)
+    pub fn scale(&self) -> f64 {
+        self.shutter_speed * self.iso * Exposure::LENS_FACTOR
+            / (78.0 * self.f_number * self.f_number)
+    }
+
+    // focal length (in meters) of a full frame camera with the given
+    // vertical field of view in degrees
+    pub fn focal_length(vfov: f64) -> f64 {
+        Exposure::SENSOR_HEIGHT / 2.0 / (vfov.to_radians() / 2.0).tan()
+    }
+
+    // radius of the lens opening for a lens with the given focal length
+    pub fn aperture_radius(&self, focal_length: f64) -> f64 {
+        focal_length / (2.0 * self.f_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-12, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn scale_follows_the_saturation_formula() {
+        // 1/100 s * ISO 100 * 0.65 / (78 * 1^2) = 0.65 / 78 = 1/120
+        let exposure = Exposure {
+            f_number: 1.0,
+            shutter_speed: 0.01,
+            iso: 100.0,
+        };
+        assert_close(exposure.scale(), 1.0 / 120.0);
+
+        // 1/50 s * ISO 400 * 0.65 / (78 * 2^2) = 5.2 / 312 = 1/60
+        let exposure = Exposure {
+            f_number: 2.0,
+            shutter_speed: 0.02,
+            iso: 400.0,
+        };
+        assert_close(exposure.scale(), 1.0 / 60.0);
+    }
+
+    #[test]
+    fn aperture_shrinks_with_the_f_number() {
+        // tan(vfov / 2) = 12mm / 50mm gives a 50mm lens
+        let vfov = 2.0 * 0.24_f64.atan().to_degrees();
+        let focal_length = Exposure::focal_length(vfov);
+        assert_close(focal_length, 0.05);
+
+        let exposure = |f_number| Exposure {
+            f_number,
+            shutter_speed: 0.01,
+            iso: 100.0,
+        };
+        assert_close(exposure(2.0).aperture_radius(focal_length), 0.0125);
+        assert_close(exposure(4.0).aperture_radius(focal_length), 0.00625);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::color::Color;
+
+use std::io::{self, Read, Write};
+
+// the running sums of the samples taken for one pixel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct FilmPixel {
+    // filter weighted sum of the sample colors, and the sums of the weights
+    // and of their magnitudes
+    pub(super) color: Color,
+    pub(super) weight: f64,
+    abs_weight: f64,
+    pub(super) samples: u32,
+    // sums of the luminance of the samples and of its square, for the
+    // variance of the pixel
+    luminance: f64,
+    luminance_squared: f64,
+}
+
+impl FilmPixel {
+    pub(super) fn new() -> Self {
+        Self {
+            color: Color::new(0.0, 0.0, 0.0),
+            weight: 0.0,
+            abs_weight: 0.0,
+            samples: 0,
+            luminance: 0.0,
+            luminance_squared: 0.0,
+        }
+    }
+
+    pub(super) fn add_sample(&mut self, color: Color, weight: f64) {
+        self.color += weight * color;
+        self.weight += weight;
+        self.abs_weight += weight.abs();
+        self.samples += 1;
+
+        let luminance = 0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z;
+        self.luminance += luminance;
+        self.luminance_squared += luminance * luminance;
+    }
+
+    // the estimated color of the pixel. normalizing by the total weight is
+    // also what makes filters with negative lobes work. with few samples
+    // those can outweigh the rest, and then the magnitudes of the weights
+    // normalize it instead, keeping their signs in the sum of colors
+    pub(super) fn average(&self) -> Color {
+        if self.weight > 0.0 {
+            self.color / self.weight
+        } else if self.abs_weight > 0.0 {
+            self.color / self.abs_weight
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    // the standard error of the mean luminance relative to the mean, so
+    // that bright and dark pixels converge to the same visible quality
+    pub(super) fn relative_error(&self) -> f64 {
+        if self.samples < 2 {
+            return f64::INFINITY;
+        }
+        let n = self.samples as f64;
+        let mean = self.luminance / n;
+        let variance = ((self.luminance_squared - n * mean * mean) / (n - 1.0)).max(0.0);
+        // dark pixels are held to an absolute floor instead, since a tiny
+        // mean would make any noise look large
+        (variance / n).sqrt() / mean.max(0.01)
+    }
+
+    // the exact sums, so that a film read back continues as if it was never
+    // written
+    pub(super) fn write(&self, mut out: impl Write) -> io::Result<()> {
+        for value in [
+            self.color.x,
+            self.color.y,
+            self.color.z,
+            self.weight,
+            self.abs_weight,
+            self.luminance,
+            self.luminance_squared,
+        ] {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        out.write_all(&self.samples.to_le_bytes())
+    }
+
+    pub(super) fn read(mut input: impl Read) -> io::Result<Self> {
+        let mut read_f64 = || -> io::Result<f64> {
+            let mut bytes = [0; 8];
+            input.read_exact(&mut bytes)?;
+            Ok(f64::from_le_bytes(bytes))
+        };
+        let color = Color::new(read_f64()?, read_f64()?, read_f64()?);
+        let (weight, abs_weight) = (read_f64()?, read_f64()?);
+        let (luminance, luminance_squared) = (read_f64()?, read_f64()?);
+        let mut samples = [0; 4];
+        input.read_exact(&mut samples)?;
+        Ok(Self {
+            color,
+            weight,
+            abs_weight,
+            samples: u32::from_le_bytes(samples),
+            luminance,
+            luminance_squared,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negative_total_weight_keeps_the_color() {
+        // a white sample outweighed by a grey one in a negative lobe
+        let mut pixel = FilmPixel::new();
+        pixel.add_sample(Color::new(1.0, 1.0, 1.0), 0.5);
+        pixel.add_sample(Color::new(0.25, 0.25, 0.25), -1.0);
+        assert_eq!(pixel.average().x, (0.5 - 0.25) / 1.5);
+        assert_eq!(FilmPixel::new().average().x, 0.0);
+    }
+}
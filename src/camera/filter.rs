@@ -0,0 +1,182 @@
+use std::f64::consts::PI;
+
+// how the samples in and around a pixel are weighted into its color. all
+// filters are separable, so they're given as a profile along one axis
+#[derive(Debug, Clone, Copy)]
+pub enum Filter {
+    // every sample within the radius counts the same
+    Box,
+    // weights fall off linearly to zero at the radius
+    Tent,
+    // a gaussian with the standard deviation in pixels, shifted down so it
+    // reaches zero at the radius
+    Gaussian { sigma: f64 },
+    // the Mitchell-Netravali cubic. b = c = 1/3 is the recommended
+    // compromise between blurring and ringing
+    Mitchell { b: f64, c: f64 },
+    // a sinc windowed by a wider sinc, with tau lobes to the window
+    Lanczos { tau: f64 },
+}
+
+impl Filter {
+    // the weight at x pixels from the pixel center, for a filter with the
+    // given radius. Mitchell and Lanczos have negative lobes
+    pub fn evaluate(&self, x: f64, radius: f64) -> f64 {
+        let x = x.abs();
+        if x > radius {
+            return 0.0;
+        }
+
+        match *self {
+            Filter::Box => 1.0,
+            Filter::Tent => radius - x,
+            Filter::Gaussian { sigma } => {
+                let gaussian = |x: f64| (-x * x / (2.0 * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(radius)).max(0.0)
+            }
+            Filter::Mitchell { b, c } => {
+                // the cubic is defined over [0, 2]
+                let x = 2.0 * x / radius;
+                if x > 1.0 {
+                    ((-b - 6.0 * c) * x * x * x
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                } else {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                }
+            }
+            Filter::Lanczos { tau } => {
+                // tau lobes of the sinc fit in the radius, where the window
+                // reaches zero
+                let x = tau * x / radius;
+                sinc(x) * sinc(x / tau)
+            }
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+// draws pixel sample offsets in proportion to the magnitude of a filter,
+// so that samples land where they matter. the weight of each sample makes
+// up for the difference from the filter itself, including its sign
+pub(super) struct FilterSampler {
+    filter: Filter,
+    radius: f64,
+    // magnitude of the filter over equal bins across [-radius, radius],
+    // and their running sum, both normalized to sum to 1
+    bins: Vec<f64>,
+    cdf: Vec<f64>,
+}
+
+impl FilterSampler {
+    const BINS: usize = 256;
+
+    pub(super) fn new(filter: Filter, radius: f64) -> Self {
+        let radius = radius.max(1e-3);
+        let bin_width = 2.0 * radius / FilterSampler::BINS as f64;
+
+        // take the largest magnitude over each bin, so that no part of the
+        // filter goes without samples
+        let mut bins: Vec<f64> = (0..FilterSampler::BINS)
+            .map(|k| {
+                let x0 = -radius + k as f64 * bin_width;
+                [x0, x0 + bin_width / 2.0, x0 + bin_width]
+                    .iter()
+                    .map(|&x| filter.evaluate(x, radius).abs())
+                    .fold(0.0, f64::max)
+            })
+            .collect();
+        let total: f64 = bins.iter().sum();
+        let mut cdf = Vec::with_capacity(bins.len());
+        let mut sum = 0.0;
+        for bin in bins.iter_mut() {
+            *bin /= total;
+            sum += *bin;
+            cdf.push(sum);
+        }
+
+        Self {
+            filter,
+            radius,
+            bins,
+            cdf,
+        }
+    }
+
+    // an offset from the pixel center in pixels, along with its weight,
+    // for a point u in [0, 1)^2
+    pub(super) fn sample(&self, u: (f64, f64)) -> ((f64, f64), f64) {
+        if let Filter::Box = self.filter {
+            // the common case, which needs no table
+            let dx = (2.0 * u.0 - 1.0) * self.radius;
+            let dy = (2.0 * u.1 - 1.0) * self.radius;
+            return ((dx, dy), 1.0);
+        }
+
+        let (dx, wx) = self.sample_1d(u.0);
+        let (dy, wy) = self.sample_1d(u.1);
+        ((dx, dy), wx * wy)
+    }
+
+    fn sample_1d(&self, u: f64) -> (f64, f64) {
+        let bin_width = 2.0 * self.radius / FilterSampler::BINS as f64;
+        let k = self
+            .cdf
+            .partition_point(|&c| c < u)
+            .min(self.bins.len() - 1);
+        let start = if k == 0 { 0.0 } else { self.cdf[k - 1] };
+        let t = ((u - start) / self.bins[k]).clamp(0.0, 1.0);
+
+        let x = -self.radius + (k as f64 + t) * bin_width;
+        let pdf = self.bins[k] / bin_width;
+        (x, self.filter.evaluate(x, self.radius) / pdf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::Pcg32;
+
+    #[test]
+    fn weights_integrate_the_filter() {
+        // the mean weight estimates the integral of the filter, which for a
+        // Mitchell filter is 1 along each axis
+        let sampler = FilterSampler::new(
+            Filter::Mitchell {
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            },
+            2.0,
+        );
+        let mut rng = Pcg32::new(17, 0);
+        let n = 200000;
+        let mean = (0..n)
+            .map(|_| sampler.sample_1d(rng.next_f64()).1)
+            .sum::<f64>()
+            / n as f64;
+        assert!((mean - 1.0).abs() < 0.02, "{}", mean);
+    }
+
+    #[test]
+    fn lanczos_fits_its_lobes_in_the_radius() {
+        let lanczos = Filter::Lanczos { tau: 2.0 };
+        for radius in [1.0, 2.0, 4.0] {
+            // the sinc crosses zero every radius / tau pixels
+            assert!(lanczos.evaluate(radius / 2.0, radius).abs() < 1e-12);
+            assert!(lanczos.evaluate(radius / 4.0, radius) > 0.5);
+            assert!(lanczos.evaluate(0.75 * radius, radius) < 0.0);
+        }
+    }
+}
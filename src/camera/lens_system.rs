@@ -0,0 +1,493 @@
+use crate::ray::Ray;
+use crate::vec3::{Point, Vec3};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// one spherical surface of a lens prescription, in meters
+#[derive(Debug, Clone, Copy)]
+struct LensElement {
+    // signed radius of curvature, or 0.0 for the aperture stop
+    curvature_radius: f64,
+    // distance along the axis to the next surface (or the film)
+    thickness: f64,
+    // index of refraction behind the surface (0.0 for the aperture stop)
+    eta: f64,
+    aperture_radius: f64,
+}
+
+// an axis-aligned rectangle on the plane of the rear lens element
+#[derive(Debug, Clone, Copy)]
+struct PupilBounds {
+    min: (f64, f64),
+    max: (f64, f64),
+}
+
+impl PupilBounds {
+    fn area(&self) -> f64 {
+        (self.max.0 - self.min.0) * (self.max.1 - self.min.1)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.max.0 < self.min.0 || self.max.1 < self.min.1
+    }
+
+    fn contains(&self, x: f64, y: f64) -> bool {
+        self.min.0 <= x && x <= self.max.0 && self.min.1 <= y && y <= self.max.1
+    }
+}
+
+// a camera lens made of spherical elements, traced ray by ray so that it
+// reproduces the distortion, vignetting and aberrations of the real lens.
+//
+// rays are traced in camera space, which has the film on the z = 0 plane,
+// the lens elements in front of it toward +z, x to the right and y up
+pub struct LensSystem {
+    // elements ordered from the front (scene side) to the rear (film side)
+    elements: Vec<LensElement>,
+    film_width: f64,
+    film_height: f64,
+    // bounds on the rear element that rays from the film can pass through,
+    // for film points at increasing distances from the axis
+    exit_pupil_bounds: Vec<PupilBounds>,
+    // the area of the largest bounds, which ray weights are relative to so
+    // that none is over 1
+    largest_pupil_area: f64,
+    focal_length: f64,
+}
+
+impl LensSystem {
+    const EXIT_PUPIL_INTERVALS: usize = 64;
+    const EXIT_PUPIL_SAMPLES: usize = 16384;
+
+    // load a prescription with one surface per line: curvature radius,
+    // thickness, index of refraction and aperture diameter, all in mm (as
+    // used by pbrt's lens files). a radius of 0 marks the aperture stop,
+    // which is opened up to aperture_diameter (mm) if given. the film is
+    // moved so that the lens focuses at focus_distance (m), and film_diagonal
+    // (mm) with the aspect ratio set the field of view
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        aperture_diameter: Option<f64>,
+        focus_distance: f64,
+        film_diagonal: f64,
+        aspect_ratio: f64,
+    ) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let mut elements = Vec::new();
+        for (line_number, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let values = line
+                .split_whitespace()
+                .map(|value| value.parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| invalid(format!("line {}: {}", line_number + 1, e)))?;
+            let [curvature_radius, thickness, eta, diameter] = values[..] else {
+                return Err(invalid(format!(
+                    "line {}: expected 4 values, found {}",
+                    line_number + 1,
+                    values.len()
+                )));
+            };
+
+            let mut diameter = diameter;
+            if curvature_radius == 0.0 {
+                if let Some(stop) = aperture_diameter {
+                    if stop > diameter {
+                        eprintln!(
+                            "aperture diameter {}mm is larger than the lens allows, using {}mm",
+                            stop, diameter
+                        );
+                    } else {
+                        diameter = stop;
+                    }
+                }
+            }
+
+            elements.push(LensElement {
+                curvature_radius: 0.001 * curvature_radius,
+                thickness: 0.001 * thickness,
+                eta,
+                aperture_radius: 0.001 * diameter / 2.0,
+            });
+        }
+        if elements.is_empty() {
+            return Err(invalid("lens prescription has no elements".to_string()));
+        }
+
+        let film_diagonal = 0.001 * film_diagonal;
+        let film_height = film_diagonal / (1.0 + aspect_ratio * aspect_ratio).sqrt();
+        let mut lens = Self {
+            elements,
+            film_width: film_height * aspect_ratio,
+            film_height,
+            exit_pupil_bounds: Vec::new(),
+            largest_pupil_area: 0.0,
+            focal_length: 0.0,
+        };
+
+        let film_distance = lens
+            .focus_thick_lens(focus_distance)
+            .ok_or_else(|| invalid(format!("lens can't focus at {}m", focus_distance)))?;
+        lens.elements.last_mut().unwrap().thickness = film_distance;
+
+        lens.exit_pupil_bounds = (0..LensSystem::EXIT_PUPIL_INTERVALS)
+            .map(|i| {
+                let r0 = i as f64 / LensSystem::EXIT_PUPIL_INTERVALS as f64 * film_diagonal / 2.0;
+                let r1 =
+                    (i + 1) as f64 / LensSystem::EXIT_PUPIL_INTERVALS as f64 * film_diagonal / 2.0;
+                lens.bound_exit_pupil(r0, r1)
+            })
+            .collect();
+        lens.largest_pupil_area = lens
+            .exit_pupil_bounds
+            .iter()
+            .map(PupilBounds::area)
+            .fold(0.0, f64::max);
+        lens.focal_length = match lens.thick_lens_approximation() {
+            Some((pz, fz)) => fz[0] - pz[0],
+            None => 0.0,
+        };
+
+        Ok(lens)
+    }
+
+    // distance from the film to the front of the lens
+    pub fn front_z(&self) -> f64 {
+        self.elements.iter().map(|element| element.thickness).sum()
+    }
+
+    fn rear_z(&self) -> f64 {
+        self.elements.last().unwrap().thickness
+    }
+
+    fn rear_aperture_radius(&self) -> f64 {
+        self.elements.last().unwrap().aperture_radius
+    }
+
+    // the effective focal length from the thick lens approximation
+    pub fn focal_length(&self) -> f64 {
+        self.focal_length
+    }
+
+    // how far across the film, as a fraction of its width, a direction at
+    // `tan` (the tangent of its angle) from the axis is imaged
+    pub fn film_offset(&self, tan: f64) -> f64 {
+        tan * self.focal_length / self.film_width
+    }
+
+    // the camera-space ray leaving the front of the lens for the film
+    // position (x, y) in [0, 1]^2 (from the top-left corner) and a sample
+    // (u1, u2) in [0, 1]^2 on the exit pupil, with the weight of the ray
+    // (cos^4 falloff and pupil size, at most 1), or None if the lens
+    // blocks it
+    pub fn generate_ray(&self, x: f64, y: f64, (u1, u2): (f64, f64)) -> Option<(Ray, f64)> {
+        // the lens flips the image, so the film is mirrored in both axes
+        let film = Point::new(
+            -(x - 0.5) * self.film_width,
+            (y - 0.5) * self.film_height,
+            0.0,
+        );
+        let (rear, bounds_area) = self.sample_exit_pupil(film.x, film.y, u1, u2);
+
+        let film_ray = Ray::new(film, rear - film);
+        let ray = self.trace_from_film(film_ray)?;
+
+        let cos_theta = film_ray.direction.unit().z;
+        let weight = cos_theta.powf(4.0) * bounds_area / self.largest_pupil_area;
+        Some((ray, weight))
+    }
+
+    // trace a camera-space ray from the film side through all of the
+    // elements, returning the ray leaving the front element
+    fn trace_from_film(&self, ray: Ray) -> Option<Ray> {
+        // flip z so the elements sit at negative z, matching the order in
+        // which their thicknesses are accumulated
+        let mut lens_ray = flip_z(ray);
+        let mut element_z = 0.0;
+
+        for i in (0..self.elements.len()).rev() {
+            let element = self.elements[i];
+            element_z -= element.thickness;
+
+            let is_stop = element.curvature_radius == 0.0;
+            let (t, normal) = if is_stop {
+                if lens_ray.direction.z >= 0.0 {
+                    return None;
+                }
+                ((element_z - lens_ray.orig.z) / lens_ray.direction.z, None)
+            } else {
+                let z_center = element_z + element.curvature_radius;
+                let (t, normal) =
+                    intersect_spherical_element(element.curvature_radius, z_center, lens_ray)?;
+                (t, Some(normal))
+            };
+
+            let hit = lens_ray.at(t);
+            if hit.x * hit.x + hit.y * hit.y > element.aperture_radius * element.aperture_radius {
+                return None;
+            }
+            lens_ray.orig = hit;
+
+            if let Some(normal) = normal {
+                let eta_i = element.eta;
+                let eta_t = if i > 0 && self.elements[i - 1].eta != 0.0 {
+                    self.elements[i - 1].eta
+                } else {
+                    1.0
+                };
+                lens_ray.direction = refract(-lens_ray.direction.unit(), normal, eta_i / eta_t)?;
+            }
+        }
+
+        Some(flip_z(lens_ray))
+    }
+
+    // trace a camera-space ray from the scene side through all of the
+    // elements, returning the ray leaving the rear element
+    fn trace_from_scene(&self, ray: Ray) -> Option<Ray> {
+        let mut lens_ray = flip_z(ray);
+        let mut element_z = -self.front_z();
+
+        for (i, element) in self.elements.iter().enumerate() {
+            let is_stop = element.curvature_radius == 0.0;
+            let (t, normal) = if is_stop {
+                ((element_z - lens_ray.orig.z) / lens_ray.direction.z, None)
+            } else {
+                let z_center = element_z + element.curvature_radius;
+                let (t, normal) =
+                    intersect_spherical_element(element.curvature_radius, z_center, lens_ray)?;
+                (t, Some(normal))
+            };
+
+            let hit = lens_ray.at(t);
+            if hit.x * hit.x + hit.y * hit.y > element.aperture_radius * element.aperture_radius {
+                return None;
+            }
+            lens_ray.orig = hit;
+
+            if let Some(normal) = normal {
+                let eta_i = if i == 0 || self.elements[i - 1].eta == 0.0 {
+                    1.0
+                } else {
+                    self.elements[i - 1].eta
+                };
+                let eta_t = if element.eta != 0.0 { element.eta } else { 1.0 };
+                lens_ray.direction = refract(-lens_ray.direction.unit(), normal, eta_i / eta_t)?;
+            }
+            element_z += element.thickness;
+        }
+
+        Some(flip_z(lens_ray))
+    }
+
+    // the z of the principal plane and focal point (in flipped z) for a ray
+    // parallel to the axis (ray_in) and the ray that comes out of the lens
+    fn cardinal_points(ray_in: Ray, ray_out: Ray) -> (f64, f64) {
+        let tf = -ray_out.orig.x / ray_out.direction.x;
+        let fz = -ray_out.at(tf).z;
+        let tp = (ray_in.orig.x - ray_out.orig.x) / ray_out.direction.x;
+        let pz = -ray_out.at(tp).z;
+        (pz, fz)
+    }
+
+    // principal planes and focal points on the scene ([0]) and film ([1])
+    // sides, found by tracing rays parallel to the axis through the lens
+    fn thick_lens_approximation(&self) -> Option<([f64; 2], [f64; 2])> {
+        // a small offset from the axis keeps the rays paraxial
+        let x = 0.001 * self.film_width.hypot(self.film_height);
+
+        let scene_ray = Ray::new(
+            Point::new(x, 0.0, self.front_z() + 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        );
+        let film_ray = self.trace_from_scene(scene_ray)?;
+        let (pz0, fz0) = LensSystem::cardinal_points(scene_ray, film_ray);
+
+        let film_ray = Ray::new(
+            Point::new(x, 0.0, self.rear_z() - 1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+        let scene_ray = self.trace_from_film(film_ray)?;
+        let (pz1, fz1) = LensSystem::cardinal_points(film_ray, scene_ray);
+
+        Some(([pz0, pz1], [fz0, fz1]))
+    }
+
+    // the distance from the rear element to the film that focuses the lens
+    // at focus_distance, using the thick lens equation
+    fn focus_thick_lens(&self, focus_distance: f64) -> Option<f64> {
+        let (pz, fz) = self.thick_lens_approximation()?;
+        let f = fz[0] - pz[0];
+        let z = -focus_distance;
+        let c = (pz[1] - z - pz[0]) * (pz[1] - z - 4.0 * f - pz[0]);
+        if c <= 0.0 {
+            return None;
+        }
+        let delta = 0.5 * (pz[1] - z + pz[0] - c.sqrt());
+        Some(self.rear_z() + delta)
+    }
+
+    // bound the region of the rear element that rays from film points at
+    // distances [r0, r1] along the x-axis can pass through
+    fn bound_exit_pupil(&self, r0: f64, r1: f64) -> PupilBounds {
+        let rear_radius = 1.5 * self.rear_aperture_radius();
+        let samples = LensSystem::EXIT_PUPIL_SAMPLES;
+        let mut bounds = PupilBounds {
+            min: (f64::INFINITY, f64::INFINITY),
+            max: (f64::NEG_INFINITY, f64::NEG_INFINITY),
+        };
+
+        for i in 0..samples {
+            let film = Point::new(r0 + (i as f64 + 0.5) / samples as f64 * (r1 - r0), 0.0, 0.0);
+            let x = -rear_radius + 2.0 * rear_radius * radical_inverse(2, i);
+            let y = -rear_radius + 2.0 * rear_radius * radical_inverse(3, i);
+
+            let rear = Point::new(x, y, self.rear_z());
+            if bounds.contains(x, y) || self.trace_from_film(Ray::new(film, rear - film)).is_some()
+            {
+                bounds.min = (bounds.min.0.min(x), bounds.min.1.min(y));
+                bounds.max = (bounds.max.0.max(x), bounds.max.1.max(y));
+            }
+        }
+
+        if bounds.is_empty() {
+            return PupilBounds {
+                min: (-rear_radius, -rear_radius),
+                max: (rear_radius, rear_radius),
+            };
+        }
+
+        // grow the bounds by about the spacing of the samples, since the
+        // true pupil can poke out between them
+        let expand = 2.0 * (2.0 * rear_radius) * std::f64::consts::SQRT_2 / (samples as f64).sqrt();
+        PupilBounds {
+            min: (bounds.min.0 - expand, bounds.min.1 - expand),
+            max: (bounds.max.0 + expand, bounds.max.1 + expand),
+        }
+    }
+
+    // a point on the rear element within the exit pupil for the film point
+    // (x, y), along with the area of the bounds it was sampled from
+    fn sample_exit_pupil(&self, x: f64, y: f64, u1: f64, u2: f64) -> (Point, f64) {
+        let r = x.hypot(y);
+        let half_diagonal = self.film_width.hypot(self.film_height) / 2.0;
+        let index = ((r / half_diagonal * LensSystem::EXIT_PUPIL_INTERVALS as f64) as usize)
+            .min(LensSystem::EXIT_PUPIL_INTERVALS - 1);
+        let bounds = self.exit_pupil_bounds[index];
+
+        let px = bounds.min.0 + u1 * (bounds.max.0 - bounds.min.0);
+        let py = bounds.min.1 + u2 * (bounds.max.1 - bounds.min.1);
+
+        // the bounds were computed along the x-axis, so rotate them to the
+        // angle of the film point
+        let (sin_theta, cos_theta) = if r != 0.0 { (y / r, x / r) } else { (0.0, 1.0) };
+        (
+            Point::new(
+                cos_theta * px - sin_theta * py,
+                sin_theta * px + cos_theta * py,
+                self.rear_z(),
+            ),
+            bounds.area(),
+        )
+    }
+}
+
+fn flip_z(ray: Ray) -> Ray {
+    Ray::new(
+        Point::new(ray.orig.x, ray.orig.y, -ray.orig.z),
+        Vec3::new(ray.direction.x, ray.direction.y, -ray.direction.z),
+    )
+}
+
+// intersect a ray with a spherical lens surface centered on the axis at
+// z_center, returning t and the normal facing against the ray
+fn intersect_spherical_element(radius: f64, z_center: f64, ray: Ray) -> Option<(f64, Vec3)> {
+    let o = ray.orig - Vec3::new(0.0, 0.0, z_center);
+    let d = ray.direction;
+    let a = Vec3::dot(d, d);
+    let b = 2.0 * Vec3::dot(d, o);
+    let c = Vec3::dot(o, o) - radius * radius;
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let t0 = (-b - discriminant.sqrt()) / (2.0 * a);
+    let t1 = (-b + discriminant.sqrt()) / (2.0 * a);
+
+    // the element is the near or far half of the sphere depending on the
+    // direction of the ray and which way the surface curves
+    let use_closer = (d.z > 0.0) ^ (radius < 0.0);
+    let t = if use_closer { t0.min(t1) } else { t0.max(t1) };
+    if t < 0.0 {
+        return None;
+    }
+
+    let mut normal = (o + t * d).unit();
+    if Vec3::dot(normal, -d) < 0.0 {
+        normal = -normal;
+    }
+    Some((t, normal))
+}
+
+// refract the direction wi (pointing away from the surface) through the
+// surface with normal n (on the side of wi), for the ratio eta = eta_i /
+// eta_t. returns None on total internal reflection
+fn refract(wi: Vec3, n: Vec3, eta: f64) -> Option<Vec3> {
+    let cos_theta_i = Vec3::dot(n, wi);
+    let sin2_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0);
+    let sin2_theta_t = eta * eta * sin2_theta_i;
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    Some(eta * -wi + (eta * cos_theta_i - cos_theta_t) * n)
+}
+
+// the digits of i in the given base mirrored around the decimal point
+fn radical_inverse(base: usize, mut i: usize) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_n = 1.0;
+    let mut reversed = 0.0;
+    while i > 0 {
+        inv_base_n *= inv_base;
+        reversed += (i % base) as f64 * inv_base_n;
+        i /= base;
+    }
+    reversed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_gauss_focal_length() {
+        let lens =
+            LensSystem::load("static/lenses/dgauss.50mm.dat", None, 10.0, 43.27, 1.5).unwrap();
+        let focal_length = 1000.0 * lens.focal_length();
+        assert!((focal_length - 50.0).abs() < 2.0, "{}", focal_length);
+
+        // an on-axis ray through the center of the pupil comes out straight
+        let (ray, weight) = lens.generate_ray(0.5, 0.5, (0.5, 0.5)).unwrap();
+        let direction = ray.direction.unit();
+        assert!(direction.x.abs() < 1e-3 && direction.y.abs() < 1e-3);
+        assert!(weight > 0.0);
+
+        // weights are relative to the largest pupil, so none is clipped
+        // when rays are dropped in proportion to them
+        for k in 0..1000 {
+            let (x, y) = ((k % 10) as f64 / 9.0, (k / 10 % 10) as f64 / 9.0);
+            let pupil = ((k / 100) as f64 / 10.0 + 0.05, (k % 7) as f64 / 7.0 + 0.05);
+            if let Some((_, weight)) = lens.generate_ray(x, y, pupil) {
+                assert!(weight <= 1.0, "{}", weight);
+            }
+        }
+    }
+}
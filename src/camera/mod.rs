@@ -0,0 +1,1116 @@
+use crate::color::{write_color, Color};
+use crate::hittable::Hittable;
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::sampler::{self, Sampler, SamplerKind};
+use crate::stats::{self, RenderStats};
+use crate::vec3::{Point, Vec3};
+
+use rayon::prelude::*;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+mod adaptive;
+mod aperture;
+mod checkpoint;
+mod crop;
+mod distortion;
+mod distributed;
+mod exposure;
+mod film;
+mod filter;
+mod lens_system;
+mod progress;
+mod progressive;
+mod projection;
+mod stereo;
+mod tiles;
+
+pub use adaptive::AdaptiveSampling;
+pub use aperture::Aperture;
+pub use crop::Crop;
+pub use distortion::Distortion;
+pub use distributed::work;
+pub use exposure::Exposure;
+pub use filter::Filter;
+pub use lens_system::LensSystem;
+pub use progressive::Progressive;
+pub use projection::{FisheyeMapping, Projection};
+pub use stereo::{Stereo, StereoLayout, StereoMethod};
+pub use tiles::TileOrder;
+
+use film::FilmPixel;
+use filter::FilterSampler;
+use progress::Progress;
+use tiles::Tile;
+
+pub struct Camera {
+    image_width: i32,
+    image_height: i32,
+    projection: Projection,
+    center: Point,
+    // camera frame: u points right, v up, and w opposite to where the
+    // camera looks
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    pixel00_loc: Point,
+    pixel_delta_u: Vec3,
+    pixel_delta_v: Vec3,
+    samples_per_pixel: i32,
+    // replaces samples_per_pixel with a per pixel sample count
+    adaptive: Option<AdaptiveSampling>,
+    progressive: Option<Progressive>,
+    // threads render the image in square tiles of this many pixels
+    tile_size: usize,
+    tile_order: TileOrder,
+    crop: Option<Crop>,
+    // hides the progress bar
+    quiet: bool,
+    max_depth: i32,
+    // reconstruction filter for the samples around each pixel
+    filter: FilterSampler,
+    sampler: SamplerKind,
+    // seeds every random choice, so the same seed renders the same image
+    seed: u64,
+
+    defocus_angle: f64,
+    defocus_disk_u: Vec3,
+    defocus_disk_v: Vec3,
+    aperture: Aperture,
+    // strength of the cat's eye vignetting of the aperture (0.0 for none)
+    cats_eye: f64,
+    // normal of the plane in focus, which is w unless the plane is tilted
+    focus_plane_normal: Vec3,
+    distortion: Option<Distortion>,
+    // multiplier from scene radiance to pixel values
+    exposure_scale: f64,
+    // traces rays through real lens elements instead of the projection
+    lens_system: Option<Arc<LensSystem>>,
+
+    stereo: Option<Stereo>,
+}
+
+impl Camera {
+    // sampler dimensions reserved for the pixel position and lens, and for
+    // each bounce after that
+    const CAMERA_DIMENSIONS: u32 = 8;
+    const BOUNCE_DIMENSIONS: u32 = 8;
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        aspect_ratio: f64,
+        image_width: i32,
+        projection: Projection,
+        samples_per_pixel: i32,
+        max_depth: i32,
+        lookfrom: Point,
+        lookat: Point,
+        vup: Vec3,
+        defocus_angle: f64,
+        focus_dist: f64,
+    ) -> Self {
+        let image_height = ((image_width as f64) / aspect_ratio) as i32;
+
+        // setup camera
+        let w = (lookfrom - lookat).unit();
+        let u = Vec3::cross(vup, w).unit();
+        let v = Vec3::cross(w, u).unit();
+
+        // setup viewport
+        let (viewport_height, defocus_angle) = match projection {
+            Projection::Perspective { vfov } => {
+                // tan(theta / 2) = h / focus_dist
+                let h = (vfov.to_radians() / 2.0).tan() * focus_dist;
+                (2.0 * h, defocus_angle)
+            }
+            // parallel rays never converge, so there is nothing to defocus
+            Projection::Orthographic { view_height } => (view_height, 0.0),
+            // panoramic rays all start at the center and don't use the
+            // viewport, which is kept only so the fields are well defined
+            _ => (2.0 * focus_dist, 0.0),
+        };
+        let viewport_width = viewport_height * (image_width as f64) / (image_height as f64);
+
+        let viewport_u = viewport_width * u;
+        let viewport_v = viewport_height * -v; // viewport_v should point downward
+
+        let pixel_delta_u = viewport_u / (image_width as f64);
+        let pixel_delta_v = viewport_v / (image_height as f64);
+
+        // compute camera center and upper-left pixel positions
+        let center = lookfrom;
+        let viewport_upper_left = center - (viewport_u / 2.0) - (viewport_v / 2.0) - focus_dist * w;
+        let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
+
+        let defocus_radius = (defocus_angle / 2.0).to_radians().tan() * focus_dist;
+        let defocus_disk_u = defocus_radius * u;
+        let defocus_disk_v = defocus_radius * v;
+
+        Self {
+            image_width,
+            image_height,
+            projection,
+            center,
+            u,
+            v,
+            w,
+            pixel00_loc,
+            pixel_delta_u,
+            pixel_delta_v,
+            samples_per_pixel,
+            adaptive: None,
+            progressive: None,
+            tile_size: 32,
+            tile_order: TileOrder::Spiral,
+            crop: None,
+            quiet: false,
+            max_depth,
+            filter: FilterSampler::new(Filter::Box, 0.5),
+            sampler: SamplerKind::Independent,
+            seed: 0,
+            defocus_angle,
+            defocus_disk_u,
+            defocus_disk_v,
+            aperture: Aperture::Circle,
+            cats_eye: 0.0,
+            focus_plane_normal: w,
+            distortion: None,
+            exposure_scale: 1.0,
+            lens_system: None,
+            stereo: None,
+        }
+    }
+
+    // weight the samples of each pixel with a reconstruction filter of the
+    // given radius in pixels, instead of averaging over the pixel square
+    // (a box of radius 0.5). samples are drawn in proportion to the filter,
+    // so wide filters cost no extra samples
+    pub fn with_filter(mut self, filter: Filter, radius: f64) -> Self {
+        self.filter = FilterSampler::new(filter, radius);
+        self
+    }
+
+    // take as many samples per pixel as it needs to converge, instead of
+    // samples_per_pixel for every pixel
+    pub fn with_adaptive_sampling(mut self, adaptive: AdaptiveSampling) -> Self {
+        self.adaptive = Some(adaptive);
+        self
+    }
+
+    // render in passes, saving the image after each one
+    pub fn with_progressive(mut self, progressive: Progressive) -> Self {
+        self.progressive = Some(progressive);
+        self
+    }
+
+    pub fn with_tiles(mut self, tile_size: usize, tile_order: TileOrder) -> Self {
+        self.tile_size = tile_size;
+        self.tile_order = tile_order;
+        self
+    }
+
+    pub fn with_crop(mut self, crop: Crop) -> Self {
+        self.crop = Some(crop);
+        self
+    }
+
+    pub fn with_quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    pub fn with_samples_per_pixel(mut self, samples_per_pixel: i32) -> Self {
+        self.samples_per_pixel = samples_per_pixel;
+        self
+    }
+
+    // draw the random numbers for each path from the given kind of sampler
+    // instead of independently
+    pub fn with_sampler(mut self, sampler: SamplerKind) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    // render with a different seed, which changes the noise but nothing
+    // else. renders with the same seed match exactly, however many threads
+    // they run on
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    // shape the defocus blur with a different lens opening. cats_eye clips
+    // the aperture toward the edges of the frame (0.0 disables it, 1.0
+    // closes it completely in the corners)
+    pub fn with_aperture(mut self, aperture: Aperture, cats_eye: f64) -> Self {
+        self.aperture = aperture;
+        self.cats_eye = cats_eye.max(0.0);
+        self
+    }
+
+    // expose the image like a physical camera. for perspective cameras the
+    // f-number also replaces the defocus angle, using the focal length of a
+    // full frame camera with the same field of view
+    pub fn with_exposure(mut self, exposure: Exposure) -> Self {
+        self.exposure_scale = exposure.scale();
+
+        if let Projection::Perspective { vfov } = self.projection {
+            let focus_dist = Vec3::dot(self.center - self.pixel00_loc, self.w);
+            let focal_length = Exposure::focal_length(vfov);
+            let defocus_radius = exposure.aperture_radius(focal_length);
+
+            self.defocus_angle = 2.0 * (defocus_radius / focus_dist).atan().to_degrees();
+            self.defocus_disk_u = defocus_radius * self.u;
+            self.defocus_disk_v = defocus_radius * self.v;
+        }
+        self
+    }
+
+    // shift the lens parallel to the film by a fraction of the image width
+    // and height (positive x moves the view right, positive y up). this
+    // frames tall buildings without pointing the camera up, which keeps
+    // vertical lines parallel
+    pub fn with_shift(mut self, shift_x: f64, shift_y: f64) -> Self {
+        let viewport_u = self.pixel_delta_u * self.image_width;
+        let viewport_v = self.pixel_delta_v * self.image_height;
+        self.pixel00_loc += shift_x * viewport_u - shift_y * viewport_v;
+        self
+    }
+
+    // tilt the plane in focus by `tilt` degrees about the horizontal axis
+    // (positive moves the top of the frame further away) and by `swing`
+    // degrees about the vertical axis (positive moves the right side further
+    // away). it still passes through the focus point on the optical axis
+    pub fn with_focal_plane_tilt(mut self, tilt: f64, swing: f64) -> Self {
+        let tilt = tilt.clamp(-89.0, 89.0).to_radians();
+        let swing = swing.clamp(-89.0, 89.0).to_radians();
+        self.focus_plane_normal = (self.w + tilt.tan() * self.v + swing.tan() * self.u).unit();
+        self
+    }
+
+    // bend the image with radial and tangential lens distortion, centered
+    // on the optical axis. only perspective cameras are distorted
+    pub fn with_distortion(mut self, distortion: Distortion) -> Self {
+        self.distortion = Some(distortion);
+        self
+    }
+
+    // shoot through a lens system, which sets its own field of view and
+    // focus and replaces the projection, defocus and aperture settings.
+    // the front of the lens is placed at lookfrom
+    pub fn with_lens_system(mut self, lens_system: Arc<LensSystem>) -> Self {
+        self.lens_system = Some(lens_system);
+        self
+    }
+
+    // render a left/right eye pair instead of a single image
+    pub fn with_stereo(mut self, stereo: Stereo) -> Self {
+        self.stereo = Some(stereo);
+        self
+    }
+
+    pub fn render(&self, world: &dyn Hittable) -> RenderStats {
+        self.render_from(world, self.new_film(), self.max_samples())
+    }
+
+    // continue the render saved in a checkpoint. its samples stay laid out
+    // for the sample count it was started with, so that raising the count
+    // adds whole new sets of well spread (e.g. stratified) samples instead
+    // of reshuffling the ones already taken
+    pub fn resume(&self, world: &dyn Hittable, checkpoint: &Path) -> io::Result<RenderStats> {
+        let input = io::BufReader::new(fs::File::open(checkpoint)?);
+        let (film, sampler_samples) =
+            checkpoint::read(input, self.output_size(), self.seed, self.sampler)?;
+        Ok(self.render_from(world, film, sampler_samples))
+    }
+
+    // sampler_samples is the sample count the sampler lays its samples out
+    // for, which is the sample count unless the render was resumed
+    fn render_from(
+        &self,
+        world: &dyn Hittable,
+        mut film: Vec<FilmPixel>,
+        sampler_samples: u32,
+    ) -> RenderStats {
+        let start = Instant::now();
+        // one reporter for the whole render, counting each pixel once a pass
+        let (rows, columns) = self.region();
+        let passes = match &self.progressive {
+            Some(progressive) => self.passes(&film, progressive).len(),
+            None => 1,
+        };
+        let pixels = rows.len() * columns.len() * passes;
+        let progress = Progress::new("Rendering".to_string(), pixels as u64, self.quiet);
+        if let Some(lens_system) = &self.lens_system {
+            let focal_length = 1000.0 * lens_system.focal_length();
+            progress.message(&format!("Lens focal length {:.1}mm", focal_length));
+        }
+        let mut stats = match &self.progressive {
+            Some(progressive) => {
+                self.render_progressive(world, &mut film, progressive, sampler_samples, &progress)
+            }
+            None => self.render_film(world, &mut film, u32::MAX, sampler_samples, &progress),
+        };
+        progress.finish();
+        stats.phases.push(("render", start.elapsed()));
+
+        let start = Instant::now();
+        self.write_image(&mut io::stdout().lock(), &film)
+            .expect("Failed to write image");
+        stats.phases.push(("output", start.elapsed()));
+        stats
+    }
+
+    fn render_progressive(
+        &self,
+        world: &dyn Hittable,
+        film: &mut [FilmPixel],
+        progressive: &Progressive,
+        sampler_samples: u32,
+        progress: &Progress,
+    ) -> RenderStats {
+        let mut stats = RenderStats::default();
+        let mut last_write = Instant::now();
+        for pass in self.passes(film, progressive) {
+            let limit = pass.saturating_mul(progressive.samples_per_pass);
+            stats.merge(self.render_film(world, film, limit, sampler_samples, progress));
+
+            let finished = self
+                .rendered_pixels(film)
+                .all(|pixel| self.pixel_done(pixel, u32::MAX));
+            let due = progressive
+                .interval
+                .is_none_or(|interval| last_write.elapsed() >= interval);
+            if finished || due {
+                if let Some(snapshot) = &progressive.snapshot {
+                    let result = write_atomically(snapshot, |out| self.write_image(out, film));
+                    report_write(progress, pass, snapshot, result);
+                }
+                if let Some(path) = &progressive.checkpoint {
+                    let result = write_atomically(path, |out| {
+                        let header = self.checkpoint_header(sampler_samples);
+                        checkpoint::write(out, &header, film)
+                    });
+                    report_write(progress, pass, path, result);
+                }
+                last_write = Instant::now();
+            }
+            if finished {
+                break;
+            }
+        }
+        stats
+    }
+
+    // the passes of a progressive render, starting at the one a resumed
+    // film got up to. every pixel is done after the last one
+    fn passes(&self, film: &[FilmPixel], progressive: &Progressive) -> Range<u32> {
+        let samples = self
+            .rendered_pixels(film)
+            .map(|pixel| pixel.samples)
+            .min()
+            .unwrap_or(0);
+        let first = samples / progressive.samples_per_pass + 1;
+        let last = self.max_samples().div_ceil(progressive.samples_per_pass);
+        first..last.max(first) + 1
+    }
+
+    // the film as a PPM image
+    fn write_image(&self, mut out: &mut dyn Write, film: &[FilmPixel]) -> io::Result<()> {
+        let (rows, columns) = self.image_region();
+        writeln!(out, "P3\n{} {}\n255", columns.len(), rows.len())?;
+        for pixel in self.pixels_in(film, (rows, columns)) {
+            write_color(&mut out, pixel.average() * self.exposure_scale, 1);
+        }
+        Ok(())
+    }
+
+    // the pixels of the film in the crop, or all of them
+    fn rendered_pixels<'a>(
+        &self,
+        film: &'a [FilmPixel],
+    ) -> impl Iterator<Item = &'a FilmPixel> + Clone {
+        self.pixels_in(film, self.region())
+    }
+
+    fn pixels_in<'a>(
+        &self,
+        film: &'a [FilmPixel],
+        (rows, columns): (Range<usize>, Range<usize>),
+    ) -> impl Iterator<Item = &'a FilmPixel> + Clone {
+        let width = self.output_size().0 as usize;
+        rows.flat_map(move |i| &film[i * width..][columns.clone()])
+    }
+
+    // an empty film for the output image, with the pixels by rows
+    fn new_film(&self) -> Vec<FilmPixel> {
+        let (output_width, output_height) = self.output_size();
+        vec![FilmPixel::new(); (output_width * output_height) as usize]
+    }
+
+    // sample every pixel of the film until it is done or has limit samples,
+    // continuing from the samples it already has
+    fn render_film(
+        &self,
+        world: &dyn Hittable,
+        film: &mut [FilmPixel],
+        limit: u32,
+        sampler_samples: u32,
+        progress: &Progress,
+    ) -> RenderStats {
+        let width = self.output_size().0 as usize;
+        let (rows, columns) = self.region();
+        let tiles = tiles::tiles(rows, columns, self.tile_size, self.tile_order);
+
+        // each thread takes the next tile in order until none are left, and
+        // renders it into its own copy so the film is only locked to copy
+        let next_tile = AtomicUsize::new(0);
+        let film = Mutex::new(film);
+        let stats = Mutex::new(RenderStats {
+            thread_busy: vec![Duration::ZERO; rayon::current_num_threads()],
+            ..Default::default()
+        });
+        (0..rayon::current_num_threads())
+            .into_par_iter()
+            .for_each(|job| {
+                // busy time is kept per pool thread, with the job standing
+                // in for it if this ever runs outside the pool
+                let thread = rayon::current_thread_index().unwrap_or(job);
+                while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::SeqCst)) {
+                    let mut tile_film: Vec<FilmPixel> = {
+                        let film = film.lock().unwrap();
+                        tile.rows
+                            .clone()
+                            .flat_map(|i| film[i * width..][tile.columns.clone()].to_vec())
+                            .collect()
+                    };
+
+                    let samples_before: u64 =
+                        tile_film.iter().map(|pixel| pixel.samples as u64).sum();
+                    let start = Instant::now();
+                    self.render_tile(world, tile, &mut tile_film, limit, sampler_samples);
+                    let mut stats = stats.lock().unwrap();
+                    stats.counters += stats::take_counters();
+                    stats.thread_busy[thread] += start.elapsed();
+                    drop(stats);
+
+                    let mut film = film.lock().unwrap();
+                    for (i, row) in tile.rows.clone().zip(tile_film.chunks(tile.columns.len())) {
+                        film[i * width..][tile.columns.clone()].copy_from_slice(row);
+                    }
+                    drop(film);
+                    let samples: u64 = tile_film.iter().map(|pixel| pixel.samples as u64).sum();
+                    progress.advance(tile_film.len() as u64, samples - samples_before);
+                }
+            });
+        stats.into_inner().unwrap()
+    }
+
+    // sample the pixels of a tile, given by rows, until they are done or
+    // have limit samples
+    fn render_tile(
+        &self,
+        world: &dyn Hittable,
+        tile: &Tile,
+        tile_film: &mut [FilmPixel],
+        limit: u32,
+        sampler_samples: u32,
+    ) {
+        let mut sampler = self.sampler.create(sampler_samples, self.seed);
+        let pixels = tile
+            .rows
+            .clone()
+            .flat_map(|i| tile.columns.clone().map(move |j| (i, j)));
+        for ((i, j), film_pixel) in pixels.zip(tile_film.iter_mut()) {
+            let pixel = (i as i32, j as i32);
+            while !self.pixel_done(film_pixel, limit) {
+                let (color, weight) =
+                    self.sample_pixel(world, pixel, film_pixel.samples, sampler.as_mut());
+                film_pixel.add_sample(color, weight);
+            }
+        }
+    }
+
+    fn checkpoint_header(&self, sampler_samples: u32) -> checkpoint::Header {
+        checkpoint::Header {
+            size: self.output_size(),
+            seed: self.seed,
+            sampler: self.sampler,
+            sampler_samples,
+        }
+    }
+
+    // the most samples any pixel can take
+    fn max_samples(&self) -> u32 {
+        match &self.adaptive {
+            Some(adaptive) => adaptive.max_samples,
+            None => self.samples_per_pixel as u32,
+        }
+    }
+
+    fn pixel_done(&self, pixel: &FilmPixel, limit: u32) -> bool {
+        if pixel.samples >= limit {
+            return true;
+        }
+        match &self.adaptive {
+            Some(adaptive) => adaptive.converged(pixel.samples, pixel.relative_error()),
+            None => pixel.samples >= self.samples_per_pixel as u32,
+        }
+    }
+
+    // the color and filter weight of sample `index` of the output pixel
+    fn sample_pixel(
+        &self,
+        world: &dyn Hittable,
+        pixel: (i32, i32),
+        index: u32,
+        sampler: &mut dyn Sampler,
+    ) -> (Color, f64) {
+        let (eye_i, eye_j, eye) = self.eye_pixel(pixel.0, pixel.1);
+        sampler.start_pixel_sample(pixel, index);
+        // choices made while intersecting use the thread's generator, so
+        // restart it on this sample's stream
+        sampler::seed_thread_rng(self.seed, sampler::sample_stream(pixel, index));
+        stats::count(|counters| counters.samples += 1);
+
+        let (offset, weight) = self.filter.sample(sampler.get_2d());
+        let color = match self.get_ray(eye_i, eye_j, offset, eye, sampler) {
+            Some(ray) => self.ray_color(ray, self.max_depth, world, sampler),
+            None => Color::new(0.0, 0.0, 0.0),
+        };
+        (color, weight)
+    }
+
+    fn ray_color(
+        &self,
+        ray: Ray,
+        depth: i32,
+        world: &dyn Hittable,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        if depth <= 0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let bounce = (self.max_depth - depth) as u32;
+        stats::count(|counters| match bounce {
+            0 => counters.camera_rays += 1,
+            _ => counters.secondary_rays += 1,
+        });
+        sampler.set_dimension(Camera::CAMERA_DIMENSIONS + bounce * Camera::BOUNCE_DIMENSIONS);
+
+        if let Some(hit_record) = world.hit(ray, Interval::new(0.001, f64::INFINITY)) {
+            // propagate the light ray if the ray is scattered. otherwise,
+            // the material absorbs all of the light, and the color is black.
+            if let Some(scattered_ray) = hit_record.material.scatter(ray, &hit_record, sampler) {
+                return scattered_ray.attenuation
+                    * self.ray_color(scattered_ray.ray, depth - 1, world, sampler);
+            }
+            return Color::new(0.0, 0.0, 0.0);
+        };
+
+        let y = 0.5 * (ray.direction.unit().y + 1.0);
+        
+
+        // blue sky 
+        Color::new(1.0, 1.0, 1.0) * (1.0 - y) + Color::new(0.4, 0.6, 1.0) * y
+        // sunset
+        // Color::new(1.0, 1.0, 1.0) * (1.0 - y) + Color::new(0.93, 0.69, 0.38) * y
+    }
+
+    // the ray for pixel (i, j) of the image seen by the eye at `eye` along u
+    // (0.0 for the mono camera), through the point `offset` pixels from the
+    // pixel center
+    fn get_ray(
+        &self,
+        i: i32,
+        j: i32,
+        offset: (f64, f64),
+        eye: f64,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Ray> {
+        // the lens comes right after the pixel position
+        sampler.set_dimension(2);
+        if let Some(lens_system) = &self.lens_system {
+            return self.get_lens_system_ray(lens_system, i, j, offset, eye, sampler);
+        }
+        if self.projection.is_panoramic() {
+            return self.get_panoramic_ray(i, j, offset, eye);
+        }
+
+        // Return a ray to the offset point around the (i, j)'th pixel
+        let pixel_center = self.pixel00_loc + self.pixel_delta_u * j + self.pixel_delta_v * i;
+        let mut pixel_sample =
+            pixel_center + offset.0 * self.pixel_delta_u + offset.1 * self.pixel_delta_v;
+
+        if let Projection::Orthographic { .. } = self.projection {
+            // start on the plane through the camera center, parallel to the
+            // viewport, and head straight at the pixel sample
+            let focus_dist = Vec3::dot(self.center - pixel_sample, self.w);
+            let origin = pixel_sample + focus_dist * self.w + eye * self.u;
+            return Some(Ray::new(origin, -self.w));
+        }
+
+        if let Some(distortion) = &self.distortion {
+            pixel_sample = self.undistort(distortion, pixel_sample);
+        }
+        if self.focus_plane_normal != self.w {
+            pixel_sample = self.tilted_focus_point(pixel_sample);
+        }
+
+        let lens_offset = if self.defocus_angle <= 0.0 {
+            Vec3::new(0.0, 0.0, 0.0)
+        } else {
+            // the lens blocks the ray entirely if the vignetted aperture
+            // has no opening for this pixel
+            self.defocus_disk_sample(i, j, sampler)? - self.center
+        };
+
+        Some(self.stereo_ray(pixel_sample, lens_offset, eye))
+    }
+
+    // the point on the focus plane that the distorted lens images onto
+    // pixel_sample
+    fn undistort(&self, distortion: &Distortion, pixel_sample: Point) -> Point {
+        // image coordinates are measured from the optical axis in units of
+        // the focus distance, which for a pinhole are the same as in units
+        // of the focal length on the film
+        let focus_dist = Vec3::dot(self.center - self.pixel00_loc, self.w);
+        let axis_point = self.center - focus_dist * self.w;
+        let offset = pixel_sample - axis_point;
+        let x = Vec3::dot(offset, self.u) / focus_dist;
+        let y = -Vec3::dot(offset, self.v) / focus_dist;
+
+        let (x, y) = distortion.undistort(x, y);
+        axis_point + focus_dist * (x * self.u - y * self.v)
+    }
+
+    // move pixel_sample along the ray from the camera center onto the tilted
+    // focus plane, so that the defocus blur converges there instead
+    fn tilted_focus_point(&self, pixel_sample: Point) -> Point {
+        let focus_dist = Vec3::dot(self.center - self.pixel00_loc, self.w);
+        let axis_point = self.center - focus_dist * self.w;
+        let direction = pixel_sample - self.center;
+
+        // rays that run parallel to (or away from) the plane are focused at
+        // infinity, which a far away point stands in for
+        let far = 1e6 * focus_dist;
+        let denominator = Vec3::dot(direction, self.focus_plane_normal);
+        let t = if denominator < 0.0 {
+            Vec3::dot(axis_point - self.center, self.focus_plane_normal) / denominator
+        } else {
+            far
+        };
+        self.center + t.min(far) * direction
+    }
+
+    fn get_panoramic_ray(&self, i: i32, j: i32, offset: (f64, f64), eye: f64) -> Option<Ray> {
+        // Return a ray from the center through the offset point of the (i, j)'th
+        // pixel, or None if the point lies outside of the projection
+        let x = (j as f64 + 0.5 + offset.0) / self.image_width as f64;
+        let y = (i as f64 + 0.5 + offset.1) / self.image_height as f64;
+        let aspect_ratio = self.image_width as f64 / self.image_height as f64;
+
+        let direction = self.projection.direction(x, y, aspect_ratio)?;
+        Some(self.stereo_panoramic_ray(direction, eye))
+    }
+
+    fn get_lens_system_ray(
+        &self,
+        lens_system: &LensSystem,
+        i: i32,
+        j: i32,
+        offset: (f64, f64),
+        eye: f64,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Ray> {
+        let x = (j as f64 + 0.5 + offset.0) / self.image_width as f64
+            + lens_system.film_offset(self.off_axis_tan(eye));
+        let y = (i as f64 + 0.5 + offset.1) / self.image_height as f64;
+        let (ray, weight) = lens_system.generate_ray(x, y, sampler.get_2d())?;
+
+        // darken by the weight (the lens's natural vignetting) by randomly
+        // dropping rays, since a ray can't carry a weight of its own
+        if weight < sampler.get_1d() {
+            return None;
+        }
+
+        // camera space has +z pointing into the scene (along -w), with the
+        // film at z = 0 and the front of the lens at the eye's center
+        let (center, u, v, w) = self.eye_frame(eye);
+        let front_z = lens_system.front_z();
+        let origin = center + ray.orig.x * u + ray.orig.y * v - (ray.orig.z - front_z) * w;
+        let direction = ray.direction.x * u + ray.direction.y * v - ray.direction.z * w;
+        Some(Ray::new(origin, direction))
+    }
+
+    fn defocus_disk_sample(&self, i: i32, j: i32, sampler: &mut dyn Sampler) -> Option<Point> {
+        // return a random point on the aperture around the camera center,
+        // where the pixel position only matters for cat's eye vignetting
+        let half_diagonal =
+            ((self.image_width as f64).hypot(self.image_height as f64) / 2.0).max(1.0);
+        let film_position = Vec3::new(
+            (j as f64 + 0.5 - self.image_width as f64 / 2.0) / half_diagonal,
+            (self.image_height as f64 / 2.0 - i as f64 - 0.5) / half_diagonal,
+            0.0,
+        );
+
+        let disk = self
+            .aperture
+            .sample_vignetted(film_position, self.cats_eye, sampler)?;
+        Some(self.center + disk.x * self.defocus_disk_u + disk.y * self.defocus_disk_v)
+    }
+}
+
+// write the file next to its path and move it into place, so the file at
+// the path is never partly written
+fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let mut out = BufWriter::new(fs::File::create(&partial)?);
+    write(&mut out)?;
+    out.flush()?;
+    drop(out);
+    fs::rename(&partial, path)
+}
+
+fn report_write(progress: &Progress, pass: u32, path: &Path, result: io::Result<()>) {
+    match result {
+        Ok(()) => progress.message(&format!("Pass {}: wrote {}", pass, path.display())),
+        Err(err) => progress.warning(&format!(
+            "Pass {}: failed to write {}: {}",
+            pass,
+            path.display(),
+            err
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::objects::{HittableList, Sphere};
+
+    fn scene() -> (HittableList, Camera) {
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Point::new(0.0, 0.0, -1.0),
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )));
+        let camera = Camera::new(
+            1.0,
+            16,
+            Projection::Perspective { vfov: 90.0 },
+            4,
+            5,
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            2.0,
+            1.0,
+        )
+        .with_seed(7);
+        (world, camera)
+    }
+
+    fn quiet() -> Progress {
+        Progress::new(String::new(), 0, true)
+    }
+
+    fn render(world: &HittableList, camera: &Camera) -> Vec<FilmPixel> {
+        let mut film = camera.new_film();
+        camera.render_film(world, &mut film, u32::MAX, camera.max_samples(), &quiet());
+        film
+    }
+
+    #[test]
+    fn same_seed_renders_identically_on_any_number_of_threads() {
+        let (world, camera) = scene();
+        let render_on = |threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| render(&world, &camera))
+        };
+        assert_eq!(render_on(1), render_on(4));
+        assert_ne!(render_on(1), render(&world, &camera.with_seed(8)));
+    }
+
+    #[test]
+    fn crops_match_the_full_render() {
+        let (world, camera) = scene();
+        // the image as lines, one per pixel after the three of the header
+        let image = |camera: &Camera| {
+            let mut image = Vec::new();
+            camera
+                .write_image(&mut image, &render(&world, camera))
+                .unwrap();
+            String::from_utf8(image)
+                .unwrap()
+                .lines()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        let full = image(&camera);
+        let crop = Crop {
+            left: 3,
+            top: 5,
+            width: 6,
+            height: 4,
+            full_frame: false,
+        };
+        let inside = |i: usize, j: usize| (5..9).contains(&i) && (3..9).contains(&j);
+
+        let cropped = image(&scene().1.with_crop(crop));
+        assert_eq!(cropped[..3], ["P3", "6 4", "255"]);
+        let expected: Vec<&String> = (0..16 * 16)
+            .filter(|k| inside(k / 16, k % 16))
+            .map(|k| &full[3 + k])
+            .collect();
+        assert_eq!(cropped[3..].iter().collect::<Vec<_>>(), expected);
+
+        // the full frame has the crop in place and black everywhere else
+        let framed = image(&camera.with_crop(Crop {
+            full_frame: true,
+            ..crop
+        }));
+        assert_eq!(framed[..3], full[..3]);
+        for k in 0..16 * 16 {
+            let expected = if inside(k / 16, k % 16) {
+                &full[3 + k]
+            } else {
+                "0 0 0"
+            };
+            assert_eq!(framed[3 + k], expected, "pixel {}", k);
+        }
+    }
+
+    #[test]
+    fn snapshots_are_complete_images() {
+        let (world, camera) = scene();
+        let dir = std::env::temp_dir().join(format!("snapshot-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let progressive = Progressive {
+            samples_per_pass: 1,
+            snapshot: Some(dir.join("snapshot.ppm")),
+            checkpoint: None,
+            interval: None,
+        };
+        let mut film = camera.new_film();
+        camera.render_progressive(&world, &mut film, &progressive, 4, &quiet());
+
+        let snapshot = fs::read_to_string(dir.join("snapshot.ppm")).unwrap();
+        let mut values = snapshot.split_whitespace();
+        assert_eq!(
+            values.by_ref().take(4).collect::<Vec<_>>(),
+            ["P3", "16", "16", "255"]
+        );
+        let colors: Vec<u32> = values.map(|value| value.parse().unwrap()).collect();
+        assert_eq!(colors.len(), 16 * 16 * 3);
+        assert!(colors.iter().all(|&value| value <= 255));
+
+        // the last snapshot is the finished image, and nothing is left over
+        let mut image = Vec::new();
+        camera.write_image(&mut image, &film).unwrap();
+        assert_eq!(snapshot.as_bytes(), image);
+        let files: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["snapshot.ppm"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resuming_a_checkpoint_finishes_the_same_render() {
+        let stratified = || scene().1.with_sampler(SamplerKind::Stratified);
+        let (world, _) = scene();
+        let camera = stratified();
+        let mut film = camera.new_film();
+        camera.render_film(&world, &mut film, 3, 4, &quiet());
+
+        let mut saved = Vec::new();
+        checkpoint::write(&mut saved, &camera.checkpoint_header(4), &film).unwrap();
+        let read = |camera: &Camera| {
+            checkpoint::read(
+                &saved[..],
+                camera.output_size(),
+                camera.seed,
+                camera.sampler,
+            )
+        };
+        let (resumed, sampler_samples) = read(&camera).unwrap();
+        assert_eq!((&resumed, sampler_samples), (&film, 4));
+        assert!(read(&stratified().with_seed(8)).is_err());
+        assert!(read(&stratified().with_sampler(SamplerKind::Halton)).is_err());
+
+        let mut finished = resumed.clone();
+        camera.render_film(&world, &mut finished, u32::MAX, sampler_samples, &quiet());
+        assert_eq!(finished, render(&world, &camera));
+
+        // raising the count adds samples with the layout of the first 4
+        let camera = stratified().with_samples_per_pixel(8);
+        let mut raised = resumed;
+        camera.render_film(&world, &mut raised, u32::MAX, sampler_samples, &quiet());
+        assert!(raised.iter().all(|pixel| pixel.samples == 8));
+
+        // so samples 4 to 7 are a second set of strata
+        let mut sampler = SamplerKind::Stratified.create(sampler_samples, camera.seed);
+        let mut strata: Vec<u32> = (4..8)
+            .map(|index| {
+                sampler.start_pixel_sample((2, 3), index);
+                (sampler.get_1d() * 4.0) as u32
+            })
+            .collect();
+        strata.sort();
+        assert_eq!(strata, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn lens_system_eyes_converge() {
+        // the chief ray, through the center of the exit pupil, never dropped
+        struct PupilCenter;
+        impl Sampler for PupilCenter {
+            fn start_pixel_sample(&mut self, _: (i32, i32), _: u32) {}
+            fn set_dimension(&mut self, _: u32) {}
+            fn get_1d(&mut self) -> f64 {
+                0.0
+            }
+            fn get_2d(&mut self) -> (f64, f64) {
+                (0.5, 0.5)
+            }
+        }
+
+        let path = "static/lenses/dgauss.50mm.dat";
+        let lens = Arc::new(LensSystem::load(path, None, 2.0, 43.27, 1.0).unwrap());
+        for method in [StereoMethod::OffAxis, StereoMethod::ToeIn] {
+            let camera = scene()
+                .1
+                .with_lens_system(lens.clone())
+                .with_stereo(Stereo {
+                    interocular: 0.1,
+                    convergence: 2.0,
+                    method,
+                    layout: StereoLayout::SideBySide,
+                });
+            for eye in [-0.05, 0.05] {
+                // the center of the 16 pixel wide image of the eye
+                let ray = camera
+                    .get_ray(7, 7, (0.5, 0.5), eye, &mut PupilCenter)
+                    .unwrap();
+                assert!((ray.orig.x - eye).abs() < 0.02, "{:?}", ray);
+                let converged = ray.at((-2.0 - ray.orig.z) / ray.direction.z);
+                assert!(converged.x.abs() < 0.005, "{:?} {:?}", method, converged);
+            }
+        }
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel_across_the_viewport() {
+        // a 4 x 2 view, with a defocus angle that parallel rays ignore
+        let camera = Camera::new(
+            2.0,
+            16,
+            Projection::Orthographic { view_height: 2.0 },
+            4,
+            5,
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            10.0,
+            3.0,
+        );
+        let mut sampler = sampler::IndependentSampler::new(1);
+
+        let (mut min_x, mut max_x) = (f64::INFINITY, f64::NEG_INFINITY);
+        for i in 0..8 {
+            for j in 0..16 {
+                for offset in [(-0.5, -0.5), (0.0, 0.0), (0.5, 0.5)] {
+                    let ray = camera.get_ray(i, j, offset, 0.0, &mut sampler).unwrap();
+                    assert_eq!(ray.direction, Vec3::new(0.0, 0.0, -1.0));
+                    assert!(ray.orig.z.abs() < 1e-12 && ray.orig.y.abs() <= 1.0 + 1e-12);
+                    min_x = min_x.min(ray.orig.x);
+                    max_x = max_x.max(ray.orig.x);
+                }
+            }
+        }
+        assert!((min_x + 2.0).abs() < 1e-12, "{}", min_x);
+        assert!((max_x - 2.0).abs() < 1e-12, "{}", max_x);
+    }
+
+    #[test]
+    fn exposure_sets_the_aperture_from_the_f_number() {
+        // a 90 degree view on a full frame sensor is a 12mm lens
+        let exposure = |f_number| Exposure {
+            f_number,
+            shutter_speed: 0.01,
+            iso: 100.0,
+        };
+        let f2 = scene().1.with_exposure(exposure(2.0));
+        let f4 = scene().1.with_exposure(exposure(4.0));
+
+        assert!((f2.defocus_disk_u.length() - 0.003).abs() < 1e-12);
+        assert!((f2.defocus_disk_v.length() - 0.003).abs() < 1e-12);
+        assert!((f4.defocus_disk_u.length() - 0.0015).abs() < 1e-12);
+        assert!(f4.defocus_angle < f2.defocus_angle);
+        assert!((f2.exposure_scale - 4.0 * f4.exposure_scale).abs() < 1e-12);
+    }
+
+    #[test]
+    fn distributed_render_survives_a_dead_worker() {
+        use distributed::{read_job, read_u32, read_u8, PROTOCOL_VERSION, TILE};
+
+        let (world, camera) = scene();
+        let camera = camera.with_tiles(4, TileOrder::Hilbert);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let job = ["test".to_string()];
+
+        std::thread::scope(|scope| {
+            let coordinator = scope.spawn(|| camera.coordinate(&world, listener, &job).unwrap());
+
+            // takes the job and a tile, then disconnects
+            let mut dead = std::net::TcpStream::connect(&address).unwrap();
+            assert_eq!(read_u32(&mut dead).unwrap(), PROTOCOL_VERSION);
+            io::Write::write_all(&mut dead, &PROTOCOL_VERSION.to_le_bytes()).unwrap();
+            assert_eq!(read_job(&mut dead).unwrap(), job);
+            let fingerprint = camera.fingerprint(&world);
+            io::Write::write_all(&mut dead, &fingerprint.to_le_bytes()).unwrap();
+            assert_eq!(read_u8(&mut dead).unwrap(), TILE);
+            let bounds: Vec<u32> = (0..4).map(|_| read_u32(&mut dead).unwrap()).collect();
+            assert!(bounds[0] < bounds[1] && bounds[2] < bounds[3]);
+            drop(dead);
+
+            // set up with another seed, so turned away
+            let different = work(&address, |_| {
+                let (world, camera) = scene();
+                (world, camera.with_seed(8).with_tiles(4, TileOrder::Hilbert))
+            });
+            assert_eq!(different.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+            for _ in 0..2 {
+                scope.spawn(|| {
+                    work(&address, |job| {
+                        assert_eq!(job, ["test"]);
+                        let (world, camera) = scene();
+                        (world, camera.with_tiles(4, TileOrder::Hilbert))
+                    })
+                    .unwrap()
+                });
+            }
+            assert_eq!(coordinator.join().unwrap().0, render(&world, &camera));
+        });
+    }
+}
@@ -0,0 +1,145 @@
+use std::io::{self, IsTerminal};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// how often the bar is redrawn on a terminal, and how often a line is
+// printed when stderr is not one (a log file or a pipe)
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const LINE_INTERVAL: Duration = Duration::from_secs(10);
+const BAR_WIDTH: usize = 30;
+
+// reports how much of the image is done, shared by the render threads. on a
+// terminal it is a bar updated in place, otherwise a line now and then
+pub(super) struct Progress {
+    label: String,
+    total_pixels: u64,
+    start: Instant,
+    quiet: bool,
+    terminal: bool,
+    state: Mutex<State>,
+}
+
+struct State {
+    pixels: u64,
+    samples: u64,
+    last_report: Instant,
+}
+
+impl Progress {
+    pub(super) fn new(label: String, total_pixels: u64, quiet: bool) -> Self {
+        let start = Instant::now();
+        Self {
+            label,
+            total_pixels,
+            start,
+            quiet,
+            terminal: io::stderr().is_terminal(),
+            state: Mutex::new(State {
+                pixels: 0,
+                samples: 0,
+                last_report: start,
+            }),
+        }
+    }
+
+    // count finished pixels and the samples they took
+    pub(super) fn advance(&self, pixels: u64, samples: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.pixels += pixels;
+        state.samples += samples;
+
+        let interval = if self.terminal {
+            REDRAW_INTERVAL
+        } else {
+            LINE_INTERVAL
+        };
+        if state.last_report.elapsed() >= interval {
+            state.last_report = Instant::now();
+            self.report(&state);
+        }
+    }
+
+    // print a message without breaking up the bar, unless quiet
+    pub(super) fn message(&self, message: &str) {
+        if !self.quiet {
+            self.print(message);
+        }
+    }
+
+    // print a message even when quiet, for things going wrong
+    pub(super) fn warning(&self, message: &str) {
+        self.print(message);
+    }
+
+    fn print(&self, message: &str) {
+        let state = self.state.lock().unwrap();
+        if self.quiet || !self.terminal {
+            return eprintln!("{}", message);
+        }
+        eprintln!("\r\x1b[K{}", message);
+        self.report(&state);
+    }
+
+    // the final report, after which the bar is left as it is. an adaptive
+    // render can finish before its last pass, so this counts as all of it
+    pub(super) fn finish(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.pixels = self.total_pixels;
+        self.report(&state);
+        if self.terminal && !self.quiet {
+            eprintln!();
+        }
+    }
+
+    fn report(&self, state: &State) {
+        if self.quiet {
+            return;
+        }
+        let fraction = state.pixels as f64 / self.total_pixels.max(1) as f64;
+        let elapsed = self.start.elapsed();
+        let eta = match fraction {
+            0.0 => "?".to_string(),
+            _ => format_duration(elapsed.mul_f64((1.0 - fraction) / fraction)),
+        };
+        let samples_per_second = state.samples as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+
+        let filled = (fraction * BAR_WIDTH as f64) as usize;
+        let line = format!(
+            "{} [{}{}] {:>3.0}%  elapsed {}  ETA {}  {} samples/s",
+            self.label,
+            "#".repeat(filled),
+            " ".repeat(BAR_WIDTH - filled),
+            100.0 * fraction,
+            format_duration(elapsed),
+            eta,
+            format_count(samples_per_second),
+        );
+        if self.terminal {
+            eprint!("\r\x1b[K{}", line);
+        } else {
+            eprintln!("{}", line);
+        }
+    }
+}
+
+// h:mm:ss, or m:ss under an hour
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+// with a k or M suffix when large
+fn format_count(count: f64) -> String {
+    if count >= 1e6 {
+        format!("{:.1}M", count / 1e6)
+    } else if count >= 1e3 {
+        format!("{:.1}k", count / 1e3)
+    } else {
+        format!("{:.0}", count)
+    }
+}
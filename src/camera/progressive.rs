@@ -0,0 +1,15 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+// renders in passes of samples_per_pass samples per pixel, writing the image
+// so far to the snapshot file and the film to the checkpoint file after each
+// pass, or only once interval has passed since the last write when it is
+// set. the files are replaced in one step, so stopping the render at any
+// time leaves the last complete ones
+#[derive(Debug, Clone)]
+pub struct Progressive {
+    pub samples_per_pass: u32,
+    pub snapshot: Option<PathBuf>,
+    pub checkpoint: Option<PathBuf>,
+    pub interval: Option<Duration>,
+}
@@ -0,0 +1,208 @@
+use std::f64::consts::PI;
+
+// how rays leave the camera
+#[derive(Debug, Clone, Copy)]
+pub enum Projection {
+    // a pinhole/thin-lens camera with a vertical field of view in degrees
+    Perspective { vfov: f64 },
+    // parallel rays through a view of the given height in world units (the
+    // width follows from the aspect ratio). there is no defocus blur
+    Orthographic { view_height: f64 },
+    // the full sphere of directions (360 x 180 degrees) as longitude and
+    // latitude, for an image with a 2:1 aspect ratio
+    Equirectangular,
+    // a circular fisheye image inscribed in the frame, covering `fov`
+    // degrees across its diameter
+    Fisheye { fov: f64, mapping: FisheyeMapping },
+    // six 90 degree faces laid out in a 3x2 grid (for a 3:2 aspect ratio):
+    // front, right, back on the top row and left, up, down on the bottom
+    Cubemap,
+}
+
+// how the angle from the optical axis maps to the distance from the image
+// center for fisheye projections
+#[derive(Debug, Clone, Copy)]
+pub enum FisheyeMapping {
+    // distance is proportional to the angle (common for dome masters)
+    Equidistant,
+    // distance is proportional to sin(angle / 2), which preserves area
+    Equisolid,
+}
+
+impl Projection {
+    // true if rays start at a single point and fan out over a sphere of
+    // directions rather than passing through a flat viewport
+    pub fn is_panoramic(&self) -> bool {
+        matches!(
+            self,
+            Projection::Equirectangular | Projection::Fisheye { .. } | Projection::Cubemap
+        )
+    }
+
+    // the direction as (right, up, forward) components in camera space for
+    // the image position (x, y) in [0, 1]^2, measured from the top-left
+    // corner. returns None where the image has no directions (outside of
+    // the fisheye circle). only valid for panoramic projections
+    pub fn direction(&self, x: f64, y: f64, aspect_ratio: f64) -> Option<(f64, f64, f64)> {
+        match *self {
+            Projection::Equirectangular => {
+                let longitude = 2.0 * PI * (x - 0.5);
+                let latitude = PI * (0.5 - y);
+                Some((
+                    latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                    latitude.cos() * longitude.cos(),
+                ))
+            }
+            Projection::Fisheye { fov, mapping } => {
+                // scale so that the circle fits the shorter side of the image
+                let (sx, sy) = if aspect_ratio >= 1.0 {
+                    (aspect_ratio, 1.0)
+                } else {
+                    (1.0, 1.0 / aspect_ratio)
+                };
+                let px = (2.0 * x - 1.0) * sx;
+                let py = (1.0 - 2.0 * y) * sy;
+                let r = (px * px + py * py).sqrt();
+                if r > 1.0 {
+                    return None;
+                }
+
+                let half_fov = fov.to_radians() / 2.0;
+                let theta = match mapping {
+                    FisheyeMapping::Equidistant => r * half_fov,
+                    FisheyeMapping::Equisolid => 2.0 * (r * (half_fov / 2.0).sin()).asin(),
+                };
+                if r == 0.0 {
+                    return Some((0.0, 0.0, 1.0));
+                }
+                Some((theta.sin() * px / r, theta.sin() * py / r, theta.cos()))
+            }
+            Projection::Cubemap => {
+                let column = ((x * 3.0) as usize).min(2);
+                let row = ((y * 2.0) as usize).min(1);
+                // position on the face in [-1, 1]^2, with b pointing up
+                let a = 2.0 * (x * 3.0 - column as f64) - 1.0;
+                let b = 1.0 - 2.0 * (y * 2.0 - row as f64);
+
+                // (forward, right, up) of each face in camera space
+                type Axis = (f64, f64, f64);
+                let faces: [(Axis, Axis, Axis); 6] = [
+                    ((0.0, 0.0, 1.0), (1.0, 0.0, 0.0), (0.0, 1.0, 0.0)), // front
+                    ((1.0, 0.0, 0.0), (0.0, 0.0, -1.0), (0.0, 1.0, 0.0)), // right
+                    ((0.0, 0.0, -1.0), (-1.0, 0.0, 0.0), (0.0, 1.0, 0.0)), // back
+                    ((-1.0, 0.0, 0.0), (0.0, 0.0, 1.0), (0.0, 1.0, 0.0)), // left
+                    ((0.0, 1.0, 0.0), (1.0, 0.0, 0.0), (0.0, 0.0, -1.0)), // up
+                    ((0.0, -1.0, 0.0), (1.0, 0.0, 0.0), (0.0, 0.0, 1.0)), // down
+                ];
+                let (forward, right, up) = faces[row * 3 + column];
+                Some((
+                    forward.0 + a * right.0 + b * up.0,
+                    forward.1 + a * right.1 + b * up.1,
+                    forward.2 + a * right.2 + b * up.2,
+                ))
+            }
+            Projection::Perspective { .. } | Projection::Orthographic { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit((x, y, z): (f64, f64, f64)) -> (f64, f64, f64) {
+        let length = (x * x + y * y + z * z).sqrt();
+        (x / length, y / length, z / length)
+    }
+
+    fn assert_close(a: (f64, f64, f64), b: (f64, f64, f64), tolerance: f64) {
+        let distance = ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2) + (a.2 - b.2).powi(2)).sqrt();
+        assert!(distance < tolerance, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn image_center_looks_forward() {
+        let forward = (0.0, 0.0, 1.0);
+        let equirectangular = Projection::Equirectangular.direction(0.5, 0.5, 2.0);
+        assert_close(equirectangular.unwrap(), forward, 1e-12);
+
+        for mapping in [FisheyeMapping::Equidistant, FisheyeMapping::Equisolid] {
+            let fisheye = Projection::Fisheye {
+                fov: 180.0,
+                mapping,
+            };
+            assert_close(fisheye.direction(0.5, 0.5, 1.0).unwrap(), forward, 1e-12);
+            // the corners are outside of the circle
+            assert!(fisheye.direction(0.0, 0.0, 1.0).is_none());
+        }
+
+        // the center of the front face, in the top-left cell of the grid
+        let cubemap = Projection::Cubemap.direction(1.0 / 6.0, 0.25, 1.5);
+        assert_close(unit(cubemap.unwrap()), forward, 1e-12);
+    }
+
+    #[test]
+    fn equirectangular_and_fisheye_edges() {
+        // the left and right edges of the panorama both look backward
+        let left = Projection::Equirectangular
+            .direction(0.0, 0.5, 2.0)
+            .unwrap();
+        let right = Projection::Equirectangular
+            .direction(1.0, 0.5, 2.0)
+            .unwrap();
+        assert_close(left, (0.0, 0.0, -1.0), 1e-12);
+        assert_close(right, (0.0, 0.0, -1.0), 1e-12);
+        let top = Projection::Equirectangular
+            .direction(0.3, 0.0, 2.0)
+            .unwrap();
+        assert_close(top, (0.0, 1.0, 0.0), 1e-12);
+
+        // a 180 degree fisheye sees sideways at the rim of its circle
+        let fisheye = Projection::Fisheye {
+            fov: 180.0,
+            mapping: FisheyeMapping::Equidistant,
+        };
+        assert_close(
+            fisheye.direction(1.0, 0.5, 1.0).unwrap(),
+            (1.0, 0.0, 0.0),
+            1e-12,
+        );
+        assert_close(
+            fisheye.direction(0.5, 0.0, 1.0).unwrap(),
+            (0.0, 1.0, 0.0),
+            1e-12,
+        );
+    }
+
+    #[test]
+    fn cube_faces_meet_along_their_shared_edges() {
+        // front | right | back share vertical edges on the top row
+        let epsilon = 1e-9;
+        for edge in [1.0 / 3.0, 2.0 / 3.0] {
+            for k in 0..=8 {
+                let y = 0.01 + 0.48 * k as f64 / 8.0;
+                let before = Projection::Cubemap
+                    .direction(edge - epsilon, y, 1.5)
+                    .unwrap();
+                let after = Projection::Cubemap
+                    .direction(edge + epsilon, y, 1.5)
+                    .unwrap();
+                assert_close(unit(before), unit(after), 1e-6);
+            }
+        }
+
+        // every face covers a 90 degree cone around its axis: the corners of
+        // each cell point along the diagonals of the cube
+        for row in 0..2 {
+            for column in 0..3 {
+                let x = (column as f64 + 0.001) / 3.0;
+                let y = (row as f64 + 0.001) / 2.0;
+                let (dx, dy, dz) = unit(Projection::Cubemap.direction(x, y, 1.5).unwrap());
+                for component in [dx, dy, dz] {
+                    assert!((component.abs() - 1.0 / 3.0_f64.sqrt()).abs() < 1e-2);
+                }
+            }
+        }
+    }
+}
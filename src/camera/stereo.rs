@@ -0,0 +1,169 @@
+use super::{Camera, Projection};
+use crate::ray::Ray;
+use crate::vec3::{Point, Vec3};
+
+// how the two eyes are aimed so that their views meet at the convergence
+// distance, where objects appear at the depth of the screen
+#[derive(Debug, Clone, Copy)]
+pub enum StereoMethod {
+    // parallel eyes with shifted (asymmetric) frustums. this avoids the
+    // vertical parallax of toe-in and is usually the more comfortable choice
+    OffAxis,
+    // each eye is rotated to look at the convergence point
+    ToeIn,
+}
+
+// where the two eye images go in the output image
+#[derive(Debug, Clone, Copy)]
+pub enum StereoLayout {
+    // left eye on the left half, right eye on the right half
+    SideBySide,
+    // left eye on the top half, right eye on the bottom half
+    TopBottom,
+}
+
+// renders a left/right pair of images. with an equirectangular projection
+// this gives omni-directional stereo (ODS), where the eyes are offset
+// perpendicular to each viewing direction as if the head turned to face it
+#[derive(Debug, Clone, Copy)]
+pub struct Stereo {
+    // distance between the eyes in world units
+    pub interocular: f64,
+    // distance at which the eyes converge, or infinity for parallel eyes
+    pub convergence: f64,
+    pub method: StereoMethod,
+    pub layout: StereoLayout,
+}
+
+impl Camera {
+    // the output image size, which holds both eyes when rendering in stereo
+    pub(super) fn output_size(&self) -> (i32, i32) {
+        match self.stereo.map(|stereo| stereo.layout) {
+            Some(StereoLayout::SideBySide) => (2 * self.image_width, self.image_height),
+            Some(StereoLayout::TopBottom) => (self.image_width, 2 * self.image_height),
+            None => (self.image_width, self.image_height),
+        }
+    }
+
+    // map the output pixel (i, j) to the pixel of the eye image it belongs
+    // to, along with the signed offset of that eye along u (0.0 if mono)
+    pub(super) fn eye_pixel(&self, i: i32, j: i32) -> (i32, i32, f64) {
+        let Some(stereo) = self.stereo else {
+            return (i, j, 0.0);
+        };
+
+        let half = stereo.interocular / 2.0;
+        match stereo.layout {
+            StereoLayout::SideBySide if j >= self.image_width => (i, j - self.image_width, half),
+            StereoLayout::TopBottom if i >= self.image_height => (i - self.image_height, j, half),
+            _ => (i, j, -half),
+        }
+    }
+
+    // the ray through pixel_sample (on the focus plane of the mono camera)
+    // for the eye at `eye` along u, where lens_offset is the sample on the
+    // defocus disk relative to the eye
+    pub(super) fn stereo_ray(&self, pixel_sample: Point, lens_offset: Vec3, eye: f64) -> Ray {
+        let Some(stereo) = self.stereo else {
+            let origin = self.center + lens_offset;
+            return Ray::new(origin, pixel_sample - origin);
+        };
+        let eye_center = self.center + eye * self.u;
+
+        if !stereo.convergence.is_finite() {
+            let origin = eye_center + lens_offset;
+            return Ray::new(origin, pixel_sample + eye * self.u - origin);
+        }
+
+        match stereo.method {
+            StereoMethod::OffAxis => {
+                // both eyes see the same point on the convergence plane
+                let focus_dist = Vec3::dot(self.center - pixel_sample, self.w);
+                let converged =
+                    self.center + (pixel_sample - self.center) * (stereo.convergence / focus_dist);
+                let target =
+                    eye_center + (converged - eye_center) * (focus_dist / stereo.convergence);
+                let origin = eye_center + lens_offset;
+                Ray::new(origin, target - origin)
+            }
+            StereoMethod::ToeIn => {
+                let (u, v, w) = self.toe_in_frame(eye_center, stereo.convergence);
+                let rotate = |d: Vec3| {
+                    Vec3::dot(d, self.u) * u + Vec3::dot(d, self.v) * v + Vec3::dot(d, self.w) * w
+                };
+
+                let target = eye_center + rotate(pixel_sample - self.center);
+                let origin = eye_center + rotate(lens_offset);
+                Ray::new(origin, target - origin)
+            }
+        }
+    }
+
+    // the mono camera frame rotated so that it looks from eye_center at
+    // the point on the optical axis at the convergence distance
+    fn toe_in_frame(&self, eye_center: Point, convergence: f64) -> (Vec3, Vec3, Vec3) {
+        let look_at = self.center - convergence * self.w;
+        let w = (eye_center - look_at).unit();
+        let u = Vec3::cross(self.v, w).unit();
+        let v = Vec3::cross(w, u);
+        (u, v, w)
+    }
+
+    // the center and (u, v, w) frame of the eye at `eye` along u, for a
+    // lens system, which forms the image itself. toe-in turns the frame,
+    // while off-axis keeps it and shifts the image by off_axis_tan instead
+    pub(super) fn eye_frame(&self, eye: f64) -> (Point, Vec3, Vec3, Vec3) {
+        let Some(stereo) = self.stereo else {
+            return (self.center, self.u, self.v, self.w);
+        };
+        let eye_center = self.center + eye * self.u;
+        match stereo.method {
+            StereoMethod::ToeIn if stereo.convergence.is_finite() => {
+                let (u, v, w) = self.toe_in_frame(eye_center, stereo.convergence);
+                (eye_center, u, v, w)
+            }
+            _ => (eye_center, self.u, self.v, self.w),
+        }
+    }
+
+    // the tangent of the angle to the right at which the eye at `eye` sees
+    // the convergence point, which an off-axis frustum puts at the center
+    pub(super) fn off_axis_tan(&self, eye: f64) -> f64 {
+        match self.stereo {
+            Some(Stereo {
+                method: StereoMethod::OffAxis,
+                convergence,
+                ..
+            }) if convergence.is_finite() => -eye / convergence,
+            _ => 0.0,
+        }
+    }
+
+    // the ray for a panoramic direction given as (right, up, forward)
+    // components in camera space, seen from the eye at `eye`
+    pub(super) fn stereo_panoramic_ray(
+        &self,
+        (right, up, forward): (f64, f64, f64),
+        eye: f64,
+    ) -> Ray {
+        let direction = right * self.u + up * self.v - forward * self.w;
+        let Some(stereo) = self.stereo else {
+            return Ray::new(self.center, direction);
+        };
+
+        let offset = match self.projection {
+            // ODS: the eyes sit on a circle, perpendicular to the horizontal
+            // part of the direction. this is left unnormalized so that the
+            // parallax fades out toward the poles instead of swirling
+            Projection::Equirectangular => eye * (forward * self.u + right * self.w),
+            _ => eye * self.u,
+        };
+        let origin = self.center + offset;
+
+        if !stereo.convergence.is_finite() {
+            return Ray::new(origin, direction);
+        }
+        let target = self.center + stereo.convergence * direction.unit();
+        Ray::new(origin, target - origin)
+    }
+}
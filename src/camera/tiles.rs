@@ -0,0 +1,119 @@
+use std::ops::Range;
+
+// the order tiles are handed to the render threads in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TileOrder {
+    // rows of tiles from the top left
+    Scanline,
+    // outwards from the center of the image, where the subject usually is,
+    // so it shows up first
+    Spiral,
+    // along a Hilbert curve, so consecutive tiles are neighbors and share
+    // more of the scene in the caches
+    Hilbert,
+}
+
+// a rectangle of output pixels, rendered by one thread at a time
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Tile {
+    pub(super) rows: Range<usize>,
+    pub(super) columns: Range<usize>,
+}
+
+// the tiles of tile_size pixels square covering the rows and columns of the
+// image in the given order. tiles at the right and bottom edges are cut
+// short
+pub(super) fn tiles(
+    rows: Range<usize>,
+    columns: Range<usize>,
+    tile_size: usize,
+    order: TileOrder,
+) -> Vec<Tile> {
+    let tile_size = tile_size.max(1);
+    let tiles_x = columns.len().div_ceil(tile_size);
+    let tiles_y = rows.len().div_ceil(tile_size);
+
+    let mut grid: Vec<(usize, usize)> = (0..tiles_y)
+        .flat_map(|y| (0..tiles_x).map(move |x| (x, y)))
+        .collect();
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            // by ring around the center tile, then by angle within the ring
+            let center = ((tiles_x as f64 - 1.0) / 2.0, (tiles_y as f64 - 1.0) / 2.0);
+            let key = |&(x, y): &(usize, usize)| {
+                let (dx, dy) = (x as f64 - center.0, y as f64 - center.1);
+                (dx.abs().max(dy.abs()), dy.atan2(dx))
+            };
+            grid.sort_by(|a, b| {
+                let (a, b) = (key(a), key(b));
+                a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1))
+            });
+        }
+        TileOrder::Hilbert => {
+            let side = tiles_x.max(tiles_y).next_power_of_two();
+            grid.sort_by_key(|&(x, y)| hilbert_index(side, x, y));
+        }
+    }
+
+    grid.into_iter()
+        .map(|(x, y)| Tile {
+            rows: rows.start + y * tile_size..(rows.start + (y + 1) * tile_size).min(rows.end),
+            columns: columns.start + x * tile_size
+                ..(columns.start + (x + 1) * tile_size).min(columns.end),
+        })
+        .collect()
+}
+
+// the distance along the Hilbert curve filling a side by side square (a
+// power of two) to the cell (x, y)
+fn hilbert_index(side: usize, mut x: usize, mut y: usize) -> usize {
+    let mut index = 0;
+    let mut s = side / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as usize;
+        let ry = (y & s > 0) as usize;
+        index += s * s * ((3 * rx) ^ ry);
+        // rotate the quadrant so the curve inside it lines up
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_the_region_once() {
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            let mut covered = vec![0; 70 * 45];
+            for tile in tiles(5..45, 3..70, 16, order) {
+                for i in tile.rows.clone() {
+                    for j in tile.columns.clone() {
+                        covered[i * 70 + j] += 1;
+                    }
+                }
+            }
+            for (k, count) in covered.iter().enumerate() {
+                let inside = k / 70 >= 5 && k % 70 >= 3;
+                assert_eq!(*count, inside as i32, "{:?}", order);
+            }
+        }
+
+        // consecutive Hilbert tiles are always neighbors
+        let hilbert = tiles(0..128, 0..128, 16, TileOrder::Hilbert);
+        for pair in hilbert.windows(2) {
+            let distance = pair[0].rows.start.abs_diff(pair[1].rows.start)
+                + pair[0].columns.start.abs_diff(pair[1].columns.start);
+            assert_eq!(distance, 16);
+        }
+    }
+}
@@ -4,11 +4,19 @@ use crate::ray::Ray;
 use crate::vec3::{Point, Vec3};
 use std::sync::Arc;
 
+#[derive(Clone)]
 pub struct HitRecord {
     // Relevant information for when a ray hits an object.
     pub point: Point,
     pub normal: Vec3,
     pub t: f64,
+    // surface coordinates of the hit, used for texture lookups
+    pub u: f64,
+    pub v: f64,
+    // partial derivatives of the surface point with respect to u and v,
+    // which span the tangent plane used by normal and bump mapping
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
     // HitRecord must track the material that was hit,
     // in order to determine the scattering later in ray_color()
     pub material: Arc<dyn Material>,
@@ -30,6 +38,10 @@ impl HitRecord {
             point,
             normal,
             t,
+            u: 0.0,
+            v: 0.0,
+            dpdu: Vec3::new(0.0, 0.0, 0.0),
+            dpdv: Vec3::new(0.0, 0.0, 0.0),
             material,
             front_face,
         }
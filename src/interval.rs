@@ -1,7 +1,7 @@
 #[derive(Debug, Clone, Copy)]
 pub struct Interval {
-    min: f64,
-    max: f64,
+    pub min: f64,
+    pub max: f64,
 }
 
 impl Interval {
@@ -5,21 +5,36 @@ mod interval;
 mod material;
 mod objects;
 mod ray;
+mod sampler;
+mod stats;
+mod texture;
 mod vec3;
 
+use std::net::TcpListener;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use material::Dielectric;
 use rand::Rng;
 
-use crate::camera::Camera;
+use crate::camera::{
+    AdaptiveSampling, Aperture, Camera, Crop, Distortion, Exposure, Filter, FisheyeMapping,
+    LensSystem, Progressive, Projection, Stereo, StereoLayout, StereoMethod, TileOrder,
+};
 use crate::color::Color;
-use crate::material::{Lambertian, Material, Metal};
+use crate::material::{
+    BumpMapped, Coated, Lambertian, Material, Metal, MixMaterial, NormalMapped, OrenNayar, ThinFilm,
+};
 use crate::objects::HittableList;
 use crate::objects::Sphere;
+use crate::objects::Subsurface;
+use crate::objects::{AlphaMask, AlphaMode};
+use crate::sampler::{Pcg32, SamplerKind};
+use crate::texture::{CheckerTexture, ImageTexture, SolidColor};
 use crate::vec3::{Point, Vec3};
 
-fn basic_world() {
+fn basic_world(stereo: Option<Stereo>) -> (HittableList, Camera) {
     // aspect ratio: width / height;
     let aspect_ratio = 16.0 / 9.0;
     let image_width = 1200;
@@ -39,7 +54,7 @@ fn basic_world() {
     let camera = Camera::new(
         aspect_ratio,
         image_width,
-        vfov,
+        Projection::Perspective { vfov },
         samples_per_pixel,
         max_depth,
         lookfrom,
@@ -48,6 +63,10 @@ fn basic_world() {
         defocus_angle,
         focus_dist,
     );
+    let camera = match stereo {
+        Some(stereo) => camera.with_stereo(stereo),
+        None => camera,
+    };
 
     // create materials
     let center: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5)));
@@ -90,10 +109,11 @@ fn basic_world() {
         Arc::clone(&ground),
     )));
 
-    camera.render(&world);
+    (world, camera)
 }
 
-fn book_cover() {
+fn book_cover(seed: u64) -> (HittableList, Camera) {
+    let mut rng = Pcg32::new(seed, 0);
     let mut world = HittableList::new();
     world.add(Arc::new(Sphere::new(
         Point::new(0.0, -1000.0, 0.0),
@@ -103,17 +123,17 @@ fn book_cover() {
 
     for a in -11..=11 {
         for b in -11..=11 {
-            let choose_mat = rand::random::<f64>();
+            let choose_mat = rng.gen::<f64>();
             let center = Point::new(
-                a as f64 + 0.9 * rand::random::<f64>(),
+                a as f64 + 0.9 * rng.gen::<f64>(),
                 0.2,
-                b as f64 + 0.9 * rand::random::<f64>(),
+                b as f64 + 0.9 * rng.gen::<f64>(),
             );
 
             if (center - Point::new(4.0, 0.2, 0.0)).length() > 0.9 {
                 if choose_mat < 0.8 {
                     // diffuse
-                    let albedo = Vec3::random() * Vec3::random();
+                    let albedo = Vec3::random(&mut rng) * Vec3::random(&mut rng);
                     world.add(Arc::new(Sphere::new(
                         center,
                         0.2,
@@ -121,8 +141,8 @@ fn book_cover() {
                     )));
                 } else if choose_mat < 0.95 {
                     // metal
-                    let albedo = Vec3::random_range(0.5, 1.0);
-                    let fuzz = rand::random::<f64>() / 2.0;
+                    let albedo = Vec3::random_range(&mut rng, 0.5, 1.0);
+                    let fuzz = rng.gen::<f64>() / 2.0;
                     world.add(Arc::new(Sphere::new(
                         center,
                         0.2,
@@ -172,7 +192,7 @@ fn book_cover() {
     let camera = Camera::new(
         aspect_ratio,
         image_width,
-        vfov,
+        Projection::Perspective { vfov },
         samples_per_pixel,
         max_depth,
         lookfrom,
@@ -182,13 +202,15 @@ fn book_cover() {
         focus_dist,
     );
 
-    camera.render(&world);
+    (world, camera)
 }
 
-fn pool_table() {
+fn pool_table(seed: u64) -> (HittableList, Camera) {
     // pool table with solid colors, low-angle shot
     // inner table spans x-axis (-8, 8) and z-axis (-4, 4)
+    #[allow(unused_variables)]
     let blue: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.1, 0.1, 0.8)));
+    #[allow(unused_variables)]
     let brown: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.3, 0.16, 0.09)));
     let rail_green: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.0, 0.3, 0.1)));
     let white: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(1.0, 1.0, 1.0)));
@@ -209,15 +231,16 @@ fn pool_table() {
     )));
 
     // balls
+    let mut rng = Pcg32::new(seed, 0);
     for color in ball_colors.iter() {
         world.add(Arc::new(Sphere::new(
             Point::new(
-                -6.0 * (rand::random::<f64>() * 2.0 - 1.0),
+                -6.0 * (rng.gen::<f64>() * 2.0 - 1.0),
                 0.5,
-                -2.0 * (rand::random::<f64>() * 2.0 - 1.0),
+                -2.0 * (rng.gen::<f64>() * 2.0 - 1.0),
             ),
             0.5,
-            Arc::clone(&color),
+            Arc::clone(color),
         )));
     }
 
@@ -273,7 +296,7 @@ fn pool_table() {
     let camera = Camera::new(
         aspect_ratio,
         image_width,
-        vfov,
+        Projection::Perspective { vfov },
         samples_per_pixel,
         max_depth,
         lookfrom,
@@ -282,12 +305,12 @@ fn pool_table() {
         defocus_angle,
         focus_dist,
     );
-    camera.render(&world);
+    (world, camera)
 }
 
-fn spheres() {
+fn spheres(seed: u64) -> (HittableList, Camera) {
     let mut world = HittableList::new();
-    let mut rng = rand::thread_rng();
+    let mut rng = Pcg32::new(seed, 0);
 
     // floor
     let sphere_mat = Arc::new(Metal::new(Color::new(0.5, 0.5, 0.5), 0.0));
@@ -318,7 +341,7 @@ fn spheres() {
     let camera = Camera::new(
         aspect_ratio,
         image_width,
-        vfov,
+        Projection::Perspective { vfov },
         samples_per_pixel,
         max_depth,
         lookfrom,
@@ -354,9 +377,984 @@ fn spheres() {
             }
         }
     }
-    camera.render(&world);
+    (world, camera)
+}
+
+fn materials() -> (HittableList, Camera) {
+    // varnished wood floor, partly rusted metal, lacquered paint, frosted
+    // glass, rough clay next to a plain diffuse sphere, a soap bubble,
+    // anodized metal with a patchy coating, a translucent wax candle, and
+    // hammered metal and dimpled clay from normal and bump maps, behind a
+    // lattice cut out of a sphere and a half-transparent veil
+    let wood: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.45, 0.25, 0.1)));
+    let floor: Arc<dyn Material> = Arc::new(Coated::new(wood, 1.5));
+
+    let steel: Arc<dyn Material> = Arc::new(Metal::new(Color::new(0.8, 0.8, 0.85), 0.05));
+    let rust: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.4, 0.15, 0.05)));
+    let rust_mask = Arc::new(CheckerTexture::new(
+        0.15,
+        Color::new(0.0, 0.0, 0.0),
+        Color::new(0.7, 0.7, 0.7),
+    ));
+    let rusted: Arc<dyn Material> = Arc::new(MixMaterial::from_mask(steel, rust, rust_mask));
+
+    let paint: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.7, 0.05, 0.05)));
+    let lacquered: Arc<dyn Material> = Arc::new(Coated::new(paint, 1.5));
+
+    let glass: Arc<dyn Material> = Arc::new(Dielectric::new(1.5));
+    let frosted: Arc<dyn Material> = Arc::new(MixMaterial::new(
+        glass,
+        Arc::new(Lambertian::new(Color::new(0.9, 0.9, 0.9))),
+        0.3,
+    ));
+
+    let clay: Arc<dyn Material> = Arc::new(OrenNayar::new(Color::new(0.75, 0.45, 0.3), 30.0));
+    let plastic: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.75, 0.45, 0.3)));
+
+    let bubble: Arc<dyn Material> =
+        Arc::new(Dielectric::with_film(1.0, ThinFilm::new(380.0, 1.33)));
+    let film_thickness = Arc::new(CheckerTexture::new(
+        0.3,
+        Color::new(250.0, 250.0, 250.0),
+        Color::new(420.0, 420.0, 420.0),
+    ));
+    let anodized: Arc<dyn Material> = Arc::new(Metal::with_film(
+        Color::new(0.9, 0.9, 0.9),
+        0.0,
+        ThinFilm::from_texture(film_thickness, 2.0),
+    ));
+
+    let normal_map = Arc::new(
+        ImageTexture::load("static/dimples_normal.ppm").expect("Failed to load normal map"),
+    );
+    let hammered: Arc<dyn Material> = Arc::new(NormalMapped::new(
+        Arc::new(Metal::new(Color::new(0.85, 0.7, 0.5), 0.05)),
+        normal_map,
+    ));
+    let height_map = Arc::new(
+        ImageTexture::load("static/dimples_height.pgm").expect("Failed to load height map"),
+    );
+    let dimpled: Arc<dyn Material> = Arc::new(BumpMapped::new(
+        Arc::new(OrenNayar::new(Color::new(0.6, 0.6, 0.65), 20.0)),
+        height_map,
+        0.02,
+    ));
+
+    let mut world = HittableList::new();
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        floor,
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, 0.6, 2.6),
+        0.6,
+        bubble,
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(-2.8, 0.5, 3.4),
+        0.5,
+        hammered,
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(2.8, 0.5, 3.4),
+        0.5,
+        dimpled,
+    )));
+
+    let lattice_alpha = Arc::new(CheckerTexture::new(
+        0.25,
+        Color::new(1.0, 1.0, 1.0),
+        Color::new(0.0, 0.0, 0.0),
+    ));
+    world.add(Arc::new(AlphaMask::new(
+        Arc::new(Sphere::new(
+            Point::new(0.0, 1.0, -3.5),
+            1.5,
+            Arc::new(Lambertian::new(Color::new(0.2, 0.5, 0.2))),
+        )),
+        lattice_alpha,
+        AlphaMode::Cutout(0.5),
+    )));
+    world.add(Arc::new(AlphaMask::new(
+        Arc::new(Sphere::new(
+            Point::new(3.6, 1.0, -2.5),
+            1.0,
+            Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.6))),
+        )),
+        Arc::new(SolidColor::new(Color::new(0.4, 0.4, 0.4))),
+        AlphaMode::Stochastic,
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(3.6, 0.7, 1.2),
+        0.7,
+        anodized,
+    )));
+    world.add(Arc::new(Subsurface::new(
+        Arc::new(Sphere::new(
+            Point::new(-3.6, 0.7, 1.2),
+            0.7,
+            Arc::new(Dielectric::new(1.45)),
+        )),
+        Color::new(0.95, 0.85, 0.6),
+        0.05,
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(-2.2, 1.0, 0.0),
+        1.0,
+        rusted,
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, 1.0, 0.0),
+        1.0,
+        lacquered,
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(2.2, 1.0, 0.0),
+        1.0,
+        frosted,
+    )));
+    world.add(Arc::new(Sphere::new(Point::new(-1.1, 0.5, 2.0), 0.5, clay)));
+    world.add(Arc::new(Sphere::new(
+        Point::new(1.1, 0.5, 2.0),
+        0.5,
+        plastic,
+    )));
+
+    let aspect_ratio = 16.0 / 9.0;
+    let image_width = 800;
+
+    // camera settings
+    let vfov = 30.0;
+    let samples_per_pixel = 200;
+    // max number of ray bounces
+    let max_depth = 20;
+    let lookfrom = Point::new(0.0, 2.5, 9.0);
+    let lookat = Point::new(0.0, 0.8, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let defocus_angle: f64 = 0.0;
+    let focus_dist = 9.0;
+
+    let camera = Camera::new(
+        aspect_ratio,
+        image_width,
+        Projection::Perspective { vfov },
+        samples_per_pixel,
+        max_depth,
+        lookfrom,
+        lookat,
+        vup,
+        defocus_angle,
+        focus_dist,
+    );
+    (world, camera)
+}
+
+fn isometric() -> (HittableList, Camera) {
+    // a grid of spheres viewed along the (1, 1, 1) diagonal with parallel rays
+    let mut world = HittableList::new();
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    let materials: Vec<Arc<dyn Material>> = vec![
+        Arc::new(Lambertian::new(Color::new(0.7, 0.2, 0.2))),
+        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.0)),
+        Arc::new(Dielectric::new(1.5)),
+        Arc::new(Lambertian::new(Color::new(0.2, 0.3, 0.7))),
+    ];
+    for i in -2..=2_i32 {
+        for j in -2..=2 {
+            world.add(Arc::new(Sphere::new(
+                Point::new(1.2 * i as f64, 0.5, 1.2 * j as f64),
+                0.5,
+                Arc::clone(&materials[(i + j).rem_euclid(4) as usize]),
+            )));
+        }
+    }
+
+    let aspect_ratio = 1.0;
+    let image_width = 600;
+
+    // camera settings
+    let view_height = 7.0; // world units covered vertically
+    let samples_per_pixel = 100;
+    // max number of ray bounces
+    let max_depth = 10;
+    let lookfrom = Point::new(10.0, 10.0, 10.0);
+    let lookat = Point::new(0.0, 0.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let focus_dist = (lookfrom - lookat).length();
+
+    let camera = Camera::new(
+        aspect_ratio,
+        image_width,
+        Projection::Orthographic { view_height },
+        samples_per_pixel,
+        max_depth,
+        lookfrom,
+        lookat,
+        vup,
+        0.0,
+        focus_dist,
+    );
+    (world, camera)
+}
+
+fn panorama(
+    projection: Projection,
+    aspect_ratio: f64,
+    stereo: Option<Stereo>,
+) -> (HittableList, Camera) {
+    // a ring of spheres around the camera, for projections that see in
+    // every direction
+    let mut world = HittableList::new();
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    let ring = 12;
+    for k in 0..ring {
+        let angle = 2.0 * std::f64::consts::PI * k as f64 / ring as f64;
+        let material: Arc<dyn Material> = match k % 3 {
+            0 => Arc::new(Lambertian::new(Color::new(0.7, 0.2, 0.2))),
+            1 => Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.05)),
+            _ => Arc::new(Dielectric::new(1.5)),
+        };
+        world.add(Arc::new(Sphere::new(
+            Point::new(4.0 * angle.cos(), 0.8, 4.0 * angle.sin()),
+            0.8,
+            material,
+        )));
+    }
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, 4.0, 0.0),
+        1.0,
+        Arc::new(Lambertian::new(Color::new(0.2, 0.3, 0.7))),
+    )));
+
+    let image_width = 800;
+
+    // camera settings
+    let samples_per_pixel = 100;
+    // max number of ray bounces
+    let max_depth = 10;
+    let lookfrom = Point::new(0.0, 1.0, 0.0);
+    let lookat = Point::new(0.0, 1.0, -1.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+
+    let camera = Camera::new(
+        aspect_ratio,
+        image_width,
+        projection,
+        samples_per_pixel,
+        max_depth,
+        lookfrom,
+        lookat,
+        vup,
+        0.0,
+        1.0,
+    );
+    let camera = match stereo {
+        Some(stereo) => camera.with_stereo(stereo),
+        None => camera,
+    };
+    (world, camera)
+}
+
+fn bokeh(aperture: Aperture) -> (HittableList, Camera) {
+    // a sphere in focus in front of out-of-focus rows of small mirror
+    // spheres, whose highlights take the shape of the aperture
+    let mut world = HittableList::new();
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::new(Color::new(0.1, 0.1, 0.1))),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Lambertian::new(Color::new(0.7, 0.3, 0.2))),
+    )));
+
+    let mirror: Arc<dyn Material> = Arc::new(Metal::new(Color::new(1.0, 1.0, 1.0), 0.0));
+    for row in 0..4 {
+        for k in -12..=12 {
+            world.add(Arc::new(Sphere::new(
+                Point::new(
+                    k as f64 * 1.5,
+                    0.5 + row as f64 * 1.5,
+                    -15.0 - row as f64 * 3.0,
+                ),
+                0.15,
+                Arc::clone(&mirror),
+            )));
+        }
+    }
+
+    let aspect_ratio = 16.0 / 9.0;
+    let image_width = 800;
+
+    // camera settings
+    let vfov = 30.0;
+    let samples_per_pixel = 200;
+    // max number of ray bounces
+    let max_depth = 10;
+    let lookfrom = Point::new(0.0, 1.5, 6.0);
+    let lookat = Point::new(0.0, 1.2, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let defocus_angle: f64 = 4.0;
+    let focus_dist = (lookfrom - lookat).length();
+
+    let camera = Camera::new(
+        aspect_ratio,
+        image_width,
+        Projection::Perspective { vfov },
+        samples_per_pixel,
+        max_depth,
+        lookfrom,
+        lookat,
+        vup,
+        defocus_angle,
+        focus_dist,
+    )
+    .with_aperture(aperture, 0.6);
+    (world, camera)
+}
+
+fn tabletop(exposure: Exposure, lens_file: Option<&str>) -> (HittableList, Camera) {
+    // marbles on a table in meters, lit by a dim sky of about 1 cd/m^2,
+    // shot with a physical exposure and optionally through a real lens
+    let mut world = HittableList::new();
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::new(Color::new(0.4, 0.3, 0.2))),
+    )));
+
+    let materials: Vec<Arc<dyn Material>> = vec![
+        Arc::new(Lambertian::new(Color::new(0.7, 0.1, 0.1))),
+        Arc::new(Dielectric::new(1.5)),
+        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.0)),
+        Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.6))),
+    ];
+    for k in 0..12 {
+        world.add(Arc::new(Sphere::new(
+            Point::new(-0.1 + 0.06 * (k % 4) as f64, 0.02, 0.3 - 0.25 * k as f64),
+            0.02,
+            Arc::clone(&materials[k % 4]),
+        )));
+    }
+
+    let aspect_ratio = 3.0 / 2.0;
+    let image_width = 600;
+
+    // camera settings
+    let vfov = 20.0; // about a 70mm lens on full frame
+    let samples_per_pixel = 200;
+    // max number of ray bounces
+    let max_depth = 10;
+    let lookfrom = Point::new(0.0, 0.1, 0.8);
+    let lookat = Point::new(0.0, 0.02, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let focus_dist = (lookfrom - lookat).length();
+
+    let camera = Camera::new(
+        aspect_ratio,
+        image_width,
+        Projection::Perspective { vfov },
+        samples_per_pixel,
+        max_depth,
+        lookfrom,
+        lookat,
+        vup,
+        0.0,
+        focus_dist,
+    )
+    .with_exposure(exposure);
+
+    let camera = match lens_file {
+        Some(lens_file) => {
+            // a full frame (36 x 24mm) sensor
+            let film_diagonal = 43.27;
+            let lens = LensSystem::load(lens_file, None, focus_dist, film_diagonal, aspect_ratio)
+                .expect("Failed to load lens");
+            camera.with_lens_system(Arc::new(lens))
+        }
+        None => camera,
+    };
+    (world, camera)
+}
+
+fn towers(
+    lookfrom: Point,
+    lookat: Point,
+    shift_y: f64,
+    tilt: Option<f64>,
+    distortion: Option<Distortion>,
+) -> (HittableList, Camera) {
+    // a block of towers stacked from spheres on a checkered ground, for
+    // architectural shifts, miniature-style tilted focus and distortion
+    let mut world = HittableList::new();
+    let checker = Arc::new(CheckerTexture::new(
+        1.0,
+        Color::new(0.2, 0.3, 0.1),
+        Color::new(0.9, 0.9, 0.9),
+    ));
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(OrenNayar::from_texture(checker, 0.0)),
+    )));
+
+    let stone: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.7, 0.6, 0.5)));
+    let roof: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.6, 0.15, 0.1)));
+    for row in 0..4 {
+        for column in -3..=3_i32 {
+            let x = column as f64 * 2.5;
+            let z = -(row as f64) * 3.0;
+            let floors = 3 + (column * 7 + row * 3).rem_euclid(5);
+            for floor in 0..floors {
+                let material = if floor == floors - 1 { &roof } else { &stone };
+                world.add(Arc::new(Sphere::new(
+                    Point::new(x, 0.4 + 0.8 * floor as f64, z),
+                    0.4,
+                    Arc::clone(material),
+                )));
+            }
+        }
+    }
+
+    let aspect_ratio = 3.0 / 2.0;
+    let image_width = 600;
+
+    // camera settings
+    let vfov = 50.0;
+    let samples_per_pixel = 100;
+    // max number of ray bounces
+    let max_depth = 10;
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    // only blur when the focus plane is tilted, so the effect shows
+    let defocus_angle = if tilt.is_some() { 2.0 } else { 0.0 };
+    let focus_dist = (lookfrom - lookat).length();
+
+    let mut camera = Camera::new(
+        aspect_ratio,
+        image_width,
+        Projection::Perspective { vfov },
+        samples_per_pixel,
+        max_depth,
+        lookfrom,
+        lookat,
+        vup,
+        defocus_angle,
+        focus_dist,
+    )
+    .with_shift(0.0, shift_y);
+    if let Some(tilt) = tilt {
+        camera = camera.with_focal_plane_tilt(tilt, 0.0);
+    }
+    if let Some(distortion) = distortion {
+        camera = camera.with_distortion(distortion);
+    }
+    (world, camera)
+}
+
+fn checkerboard(filter: Filter, radius: f64) -> (HittableList, Camera) {
+    // a fine checkerboard running off to the horizon, where the squares get
+    // smaller than a pixel and the reconstruction filter decides between
+    // aliasing and blur
+    let mut world = HittableList::new();
+    let checker = Arc::new(CheckerTexture::new(
+        0.5,
+        Color::new(0.05, 0.05, 0.05),
+        Color::new(0.95, 0.95, 0.95),
+    ));
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(OrenNayar::from_texture(checker, 0.0)),
+    )));
+
+    let aspect_ratio = 16.0 / 9.0;
+    let image_width = 400;
+
+    // camera settings
+    let vfov = 40.0;
+    let samples_per_pixel = 64;
+    // max number of ray bounces
+    let max_depth = 10;
+    let lookfrom = Point::new(0.0, 1.0, 0.0);
+    let lookat = Point::new(2.0, 0.6, -10.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let defocus_angle = 0.0;
+    let focus_dist = 10.0;
+
+    let camera = Camera::new(
+        aspect_ratio,
+        image_width,
+        Projection::Perspective { vfov },
+        samples_per_pixel,
+        max_depth,
+        lookfrom,
+        lookat,
+        vup,
+        defocus_angle,
+        focus_dist,
+    )
+    .with_filter(filter, radius);
+    (world, camera)
+}
+
+fn noise(sampler: SamplerKind) -> (HittableList, Camera) {
+    // diffuse spheres under the sky with defocus blur at a low sample
+    // count, to compare the noise left by each sampler
+    let mut world = HittableList::new();
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Lambertian::new(Color::new(0.7, 0.3, 0.2))),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(-2.2, 0.6, 1.5),
+        0.6,
+        Arc::new(Lambertian::new(Color::new(0.2, 0.4, 0.7))),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(2.0, 0.5, -2.5),
+        0.5,
+        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.3)),
+    )));
+
+    let aspect_ratio = 16.0 / 9.0;
+    let image_width = 400;
+
+    // camera settings
+    let vfov = 30.0;
+    let samples_per_pixel = 16;
+    // max number of ray bounces
+    let max_depth = 10;
+    let lookfrom = Point::new(0.0, 2.0, 9.0);
+    let lookat = Point::new(0.0, 0.8, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let defocus_angle: f64 = 2.0;
+    let focus_dist = (lookfrom - lookat).length();
+
+    let camera = Camera::new(
+        aspect_ratio,
+        image_width,
+        Projection::Perspective { vfov },
+        samples_per_pixel,
+        max_depth,
+        lookfrom,
+        lookat,
+        vup,
+        defocus_angle,
+        focus_dist,
+    )
+    .with_sampler(sampler);
+    (world, camera)
+}
+
+// what to do once the scene is set up
+struct Run {
+    mode: Mode,
+    // where to write the render statistics as JSON
+    stats_json: Option<PathBuf>,
+    // leave stderr to errors
+    quiet: bool,
+}
+
+enum Mode {
+    Render,
+    Resume(PathBuf),
+    // render on the workers that connect to the address
+    Coordinate(String),
 }
 
 fn main() {
-    basic_world()
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("--worker") {
+        let coordinator = args
+            .get(1)
+            .unwrap_or_else(|| usage("--worker needs the address of the coordinator"));
+        camera::work(coordinator, |job| {
+            let (world, camera, _) = setup(job);
+            (world, camera)
+        })
+        .unwrap_or_else(|err| {
+            eprintln!("Worker failed: {}", err);
+            std::process::exit(1);
+        });
+        return;
+    }
+
+    let start = Instant::now();
+    let (world, camera, run) = setup(&args);
+    let setup_time = start.elapsed();
+
+    let mut stats = match run.mode {
+        Mode::Render => camera.render(&world),
+        Mode::Resume(path) => camera.resume(&world, &path).unwrap_or_else(|err| {
+            eprintln!("Failed to resume from {}: {}", path.display(), err);
+            std::process::exit(1);
+        }),
+        Mode::Coordinate(address) => {
+            // the workers get the same command line, without this option
+            let mut job = Vec::new();
+            let mut rest = args.iter();
+            while let Some(arg) = rest.next() {
+                if arg == "--coordinate" {
+                    rest.next();
+                } else {
+                    job.push(arg.clone());
+                }
+            }
+            let listener = TcpListener::bind(&address).unwrap_or_else(|err| {
+                eprintln!("Failed to listen on {}: {}", address, err);
+                std::process::exit(1);
+            });
+            camera
+                .render_distributed(&world, listener, &job)
+                .unwrap_or_else(|err| {
+                    eprintln!("Distributed render failed: {}", err);
+                    std::process::exit(1);
+                })
+        }
+    };
+
+    stats.phases.insert(0, ("setup", setup_time));
+    if !run.quiet {
+        eprint!("{}", stats);
+    }
+    if let Some(path) = run.stats_json {
+        if let Err(err) = std::fs::write(&path, stats.to_json()) {
+            eprintln!("Failed to write {}: {}", path.display(), err);
+        }
+    }
+}
+
+// the scene and camera picked by the first command line argument, with
+// options after it
+fn setup(args: &[String]) -> (HittableList, Camera, Run) {
+    let mut args = args.iter().cloned();
+    let scene = args.next().unwrap_or_else(|| "basic".to_string());
+    let mut seed = 0;
+    let mut samples_per_pass = None;
+    let mut snapshot = None;
+    let mut interval = None;
+    let mut checkpoint = None;
+    let mut resume = None;
+    let mut samples_per_pixel = None;
+    let mut tile_size = 32;
+    let mut tile_order = TileOrder::Spiral;
+    let mut crop = None;
+    let mut full_frame = false;
+    let mut coordinate = None;
+    let mut stats_json = None;
+    let mut quiet = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                seed = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or_else(|| usage("--seed needs a number"));
+            }
+            "--progressive" => {
+                samples_per_pass = Some(
+                    args.next()
+                        .and_then(|value| value.parse().ok())
+                        .filter(|&samples| samples > 0)
+                        .unwrap_or_else(|| usage("--progressive needs a number of samples")),
+                );
+            }
+            "--snapshot" => {
+                snapshot = Some(PathBuf::from(
+                    args.next()
+                        .unwrap_or_else(|| usage("--snapshot needs a file")),
+                ));
+            }
+            "--checkpoint" => {
+                checkpoint = Some(PathBuf::from(
+                    args.next()
+                        .unwrap_or_else(|| usage("--checkpoint needs a file")),
+                ));
+            }
+            "--resume" => {
+                resume = Some(PathBuf::from(
+                    args.next()
+                        .unwrap_or_else(|| usage("--resume needs a file")),
+                ));
+            }
+            "--samples" => {
+                samples_per_pixel = Some(
+                    args.next()
+                        .and_then(|value| value.parse().ok())
+                        .filter(|&samples| samples > 0)
+                        .unwrap_or_else(|| usage("--samples needs a number")),
+                );
+            }
+            "--tile-size" => {
+                tile_size = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .filter(|&size| size > 0)
+                    .unwrap_or_else(|| usage("--tile-size needs a number of pixels"));
+            }
+            "--tile-order" => {
+                tile_order = match args.next().as_deref() {
+                    Some("scanline") => TileOrder::Scanline,
+                    Some("spiral") => TileOrder::Spiral,
+                    Some("hilbert") => TileOrder::Hilbert,
+                    _ => usage("--tile-order needs scanline, spiral or hilbert"),
+                };
+            }
+            "--crop" => {
+                // left,top,width,height in pixels
+                let values: Option<Vec<usize>> = args
+                    .next()
+                    .map(|value| value.split(',').map(|v| v.parse().ok()).collect())
+                    .unwrap_or_default();
+                crop = match values.as_deref() {
+                    Some(&[left, top, width, height]) => Some((left, top, width, height)),
+                    _ => usage("--crop needs <left>,<top>,<width>,<height>"),
+                };
+            }
+            "--full-frame" => full_frame = true,
+            "--quiet" | "-q" => quiet = true,
+            "--stats-json" => {
+                stats_json = Some(PathBuf::from(
+                    args.next()
+                        .unwrap_or_else(|| usage("--stats-json needs a file")),
+                ));
+            }
+            "--coordinate" => {
+                coordinate = Some(
+                    args.next()
+                        .unwrap_or_else(|| usage("--coordinate needs an address to listen on")),
+                );
+            }
+            "--snapshot-interval" => {
+                interval = Some(Duration::from_secs_f64(
+                    args.next()
+                        .and_then(|value| value.parse().ok())
+                        .filter(|&seconds: &f64| seconds >= 0.0)
+                        .unwrap_or_else(|| usage("--snapshot-interval needs a number of seconds")),
+                ));
+            }
+            _ => usage(&format!("unknown option: {}", arg)),
+        }
+    }
+
+    let (world, camera) = match scene.as_str() {
+        "basic" => basic_world(None),
+        "stereo" => basic_world(Some(Stereo {
+            interocular: 0.065,
+            convergence: 3.4,
+            method: StereoMethod::OffAxis,
+            layout: StereoLayout::SideBySide,
+        })),
+        "stereo_toe_in" => basic_world(Some(Stereo {
+            interocular: 0.065,
+            convergence: 3.4,
+            method: StereoMethod::ToeIn,
+            layout: StereoLayout::SideBySide,
+        })),
+        "book_cover" => book_cover(seed),
+        "pool_table" => pool_table(seed),
+        "spheres" => spheres(seed),
+        "materials" => materials(),
+        "isometric" => isometric(),
+        "equirectangular" => panorama(Projection::Equirectangular, 2.0, None),
+        "ods" => panorama(
+            Projection::Equirectangular,
+            2.0,
+            Some(Stereo {
+                interocular: 0.065,
+                convergence: f64::INFINITY,
+                method: StereoMethod::OffAxis,
+                layout: StereoLayout::TopBottom,
+            }),
+        ),
+        "fisheye" => panorama(
+            Projection::Fisheye {
+                fov: 180.0,
+                mapping: FisheyeMapping::Equidistant,
+            },
+            1.0,
+            None,
+        ),
+        "fisheye_equisolid" => panorama(
+            Projection::Fisheye {
+                fov: 220.0,
+                mapping: FisheyeMapping::Equisolid,
+            },
+            1.0,
+            None,
+        ),
+        "cubemap" => panorama(Projection::Cubemap, 1.5, None),
+        // two exposures of equal brightness, wide open and stopped down
+        "exposure" => tabletop(
+            Exposure {
+                f_number: 2.0,
+                shutter_speed: 0.5,
+                iso: 1000.0,
+            },
+            None,
+        ),
+        "exposure_stopped_down" => tabletop(
+            Exposure {
+                f_number: 11.0,
+                shutter_speed: 15.0,
+                iso: 1000.0,
+            },
+            None,
+        ),
+        "double_gauss" => tabletop(
+            Exposure {
+                f_number: 2.0,
+                shutter_speed: 0.5,
+                iso: 1000.0,
+            },
+            Some("static/lenses/dgauss.50mm.dat"),
+        ),
+        "bokeh" => bokeh(Aperture::Polygon {
+            blades: 6,
+            rotation: 15.0,
+        }),
+        "bokeh_mask" => bokeh(Aperture::Mask(Arc::new(
+            ImageTexture::load("static/aperture_star.pgm").expect("Failed to load aperture mask"),
+        ))),
+        // keep the camera level and shift up to fit the towers in
+        "shift" => towers(
+            Point::new(0.0, 1.5, 10.0),
+            Point::new(0.0, 1.5, 0.0),
+            0.3,
+            None,
+            None,
+        ),
+        // looking down with a steep focus plane gives a thin band of focus
+        "miniature" => towers(
+            Point::new(0.0, 12.0, 14.0),
+            Point::new(0.0, 0.0, -4.0),
+            0.0,
+            Some(-30.0),
+            None,
+        ),
+        "distortion" => towers(
+            Point::new(0.0, 1.5, 10.0),
+            Point::new(0.0, 1.5, 0.0),
+            0.0,
+            None,
+            Some(Distortion {
+                k1: -0.25,
+                k2: 0.05,
+                p1: 0.01,
+                ..Default::default()
+            }),
+        ),
+        "filter_box" => checkerboard(Filter::Box, 0.5),
+        "filter_tent" => checkerboard(Filter::Tent, 1.0),
+        "filter_gaussian" => checkerboard(Filter::Gaussian { sigma: 0.5 }, 1.5),
+        "filter_mitchell" => checkerboard(
+            Filter::Mitchell {
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            },
+            2.0,
+        ),
+        "filter_lanczos" => checkerboard(Filter::Lanczos { tau: 3.0 }, 3.0),
+        "sampler_independent" => noise(SamplerKind::Independent),
+        "sampler_stratified" => noise(SamplerKind::Stratified),
+        "sampler_halton" => noise(SamplerKind::Halton),
+        "sampler_sobol" => noise(SamplerKind::Sobol),
+        "sampler_blue_noise" => noise(SamplerKind::BlueNoise),
+        "adaptive" => {
+            // the same scene with samples spent where it is still noisy
+            let (world, camera) = noise(SamplerKind::Sobol);
+            let camera = camera.with_adaptive_sampling(AdaptiveSampling {
+                min_samples: 16,
+                max_samples: 1024,
+                max_relative_error: 0.02,
+            });
+            (world, camera)
+        }
+        _ => usage(&format!("unknown scene: {}", scene)),
+    };
+    let mut camera = camera
+        .with_seed(seed)
+        .with_tiles(tile_size, tile_order)
+        .with_quiet(quiet);
+    if let Some(samples_per_pixel) = samples_per_pixel {
+        camera = camera.with_samples_per_pixel(samples_per_pixel);
+    }
+    if let Some((left, top, width, height)) = crop {
+        camera = camera.with_crop(Crop {
+            left,
+            top,
+            width,
+            height,
+            full_frame,
+        });
+    } else if full_frame {
+        usage("--full-frame needs --crop");
+    }
+    // checkpoints are written between passes, so they render in passes
+    // even without --progressive
+    let samples_per_pass = match (samples_per_pass, &checkpoint) {
+        (Some(samples_per_pass), _) => Some(samples_per_pass),
+        (None, Some(_)) => Some(16),
+        (None, None) => None,
+    };
+    if samples_per_pass.is_none() && snapshot.is_some() {
+        usage("--snapshot needs --progressive");
+    }
+    if let Some(samples_per_pass) = samples_per_pass {
+        if snapshot.is_none() && checkpoint.is_none() {
+            usage("--progressive needs --snapshot or --checkpoint");
+        }
+        camera = camera.with_progressive(Progressive {
+            samples_per_pass,
+            snapshot,
+            checkpoint,
+            interval,
+        });
+    }
+    let mode = match (resume, coordinate) {
+        (None, None) => Mode::Render,
+        (Some(path), None) => Mode::Resume(path),
+        (None, Some(address)) if samples_per_pass.is_none() => Mode::Coordinate(address),
+        _ => usage("--coordinate renders in one go, without checkpoints or passes"),
+    };
+    (
+        world,
+        camera,
+        Run {
+            mode,
+            stats_json,
+            quiet,
+        },
+    )
+}
+
+fn usage(message: &str) -> ! {
+    eprintln!("{}", message);
+    eprintln!(
+        "usage: raytracer [scene] [--seed <number>] [--samples <number>] \
+         [--progressive <samples per pass>] [--snapshot <file>] [--checkpoint <file>] \
+         [--snapshot-interval <seconds>] [--resume <checkpoint>] [--tile-size <pixels>] \
+         [--tile-order scanline|spiral|hilbert] [--crop <left>,<top>,<width>,<height> \
+         [--full-frame]] [--coordinate <address>] [--stats-json <file>] [--quiet]\n       raytracer --worker <address>"
+    );
+    std::process::exit(1);
 }
@@ -1,12 +1,21 @@
 use crate::color::Color;
 use crate::hittable::HitRecord;
 use crate::ray::{Ray, ScatteredRay};
-use crate::vec3::{random_unit_vector, reflect, refract, Vec3};
+use crate::sampler::Sampler;
+use crate::texture::{SolidColor, Texture};
+use crate::vec3::{reflect, refract, sample_unit_vector, Vec3};
+use std::sync::Arc;
 
 pub trait Material: Send + Sync {
     // describe the scattered ray (ray and attenuation color) off of the
-    // material, given an incident ray with hit_record hit
-    fn scatter(&self, ray: Ray, hit_record: &HitRecord) -> Option<ScatteredRay>;
+    // material, given an incident ray with hit_record hit. random choices
+    // are drawn from the sampler
+    fn scatter(
+        &self,
+        ray: Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatteredRay>;
 }
 
 // a diffuse surface material that scatters rays with a cos distribution
@@ -23,8 +32,13 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray: Ray, hit_record: &HitRecord) -> Option<ScatteredRay> {
-        let mut direction = hit_record.normal + random_unit_vector();
+    fn scatter(
+        &self,
+        _ray: Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatteredRay> {
+        let mut direction = hit_record.normal + sample_unit_vector(sampler.get_2d());
 
         // drop the case where the random unit sphere points backward
         // and results in a zero scatter vector
@@ -39,12 +53,87 @@ impl Material for Lambertian {
     }
 }
 
+// a rough diffuse surface (clay, concrete, fabric) using the Oren-Nayar
+// microfacet model. sigma is the standard deviation of the facet slopes in
+// degrees; sigma = 0 reduces to Lambertian
+pub struct OrenNayar {
+    albedo: Arc<dyn Texture>,
+    a: f64,
+    b: f64,
+}
+
+impl OrenNayar {
+    pub fn new(albedo: Color, sigma: f64) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)), sigma)
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>, sigma: f64) -> Self {
+        let sigma2 = sigma.to_radians().powf(2.0);
+        Self {
+            albedo,
+            a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+}
+
+impl Material for OrenNayar {
+    fn scatter(
+        &self,
+        ray: Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatteredRay> {
+        // sample with a cos distribution like Lambertian, so the cos term
+        // and pdf cancel and only the Oren-Nayar factor remains
+        let mut direction = hit_record.normal + sample_unit_vector(sampler.get_2d());
+        if direction.near_zero() {
+            direction = hit_record.normal;
+        }
+
+        let normal = hit_record.normal;
+        let wi = direction.unit();
+        let wo = -ray.direction.unit();
+        let cos_i = Vec3::dot(wi, normal).clamp(-1.0, 1.0);
+        let cos_o = Vec3::dot(wo, normal).clamp(-1.0, 1.0);
+        let sin_i = (1.0 - cos_i * cos_i).max(0.0).sqrt();
+        let sin_o = (1.0 - cos_o * cos_o).max(0.0).sqrt();
+
+        // cos of the azimuthal angle between the two directions, found by
+        // projecting them onto the tangent plane
+        let tangent_i = wi - cos_i * normal;
+        let tangent_o = wo - cos_o * normal;
+        let max_cos = if tangent_i.near_zero() || tangent_o.near_zero() {
+            0.0
+        } else {
+            Vec3::dot(tangent_i.unit(), tangent_o.unit()).max(0.0)
+        };
+
+        // alpha is the larger of the two polar angles and beta the smaller
+        let (sin_alpha, tan_beta) = if cos_i.abs() > cos_o.abs() {
+            (sin_o, sin_i / cos_i.abs())
+        } else {
+            (sin_i, sin_o / cos_o.abs().max(1e-8))
+        };
+
+        let albedo = self
+            .albedo
+            .value(hit_record.u, hit_record.v, hit_record.point);
+        Some(ScatteredRay {
+            ray: Ray::new(hit_record.point, direction),
+            attenuation: albedo * (self.a + self.b * max_cos * sin_alpha * tan_beta),
+        })
+    }
+}
+
 // a material that mirror reflects all of the light that hits the surface
 pub struct Metal {
     // reduction in intensity/color of a ray that hits the surface
     albedo: Color,
     // magnitude of a random unit vector added to the scatter vector
     fuzz: f64,
+    // optional coating that tints the reflection by interference
+    film: Option<ThinFilm>,
 }
 
 impl Metal {
@@ -52,37 +141,73 @@ impl Metal {
         Self {
             albedo,
             fuzz: fuzz.clamp(0.0, 1.0),
+            film: None,
+        }
+    }
+
+    pub fn with_film(albedo: Color, fuzz: f64, film: ThinFilm) -> Self {
+        Self {
+            film: Some(film),
+            ..Self::new(albedo, fuzz)
         }
     }
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: Ray, hit_record: &HitRecord) -> Option<ScatteredRay> {
+    fn scatter(
+        &self,
+        ray: Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatteredRay> {
         // mirror the ray that hits the object against the normal of the hit
-        let reflected =
-            reflect(ray.direction.unit(), hit_record.normal) + self.fuzz * random_unit_vector();
+        let unit_direction = ray.direction.unit();
+        let reflected = reflect(unit_direction, hit_record.normal)
+            + self.fuzz * sample_unit_vector(sampler.get_2d());
+
+        let attenuation = match &self.film {
+            Some(film) => film.reflectance(
+                hit_record,
+                Vec3::dot(unit_direction, -hit_record.normal),
+                1.0,
+                Substrate::Conductor(self.albedo),
+            ),
+            None => self.albedo,
+        };
 
         Some(ScatteredRay {
             ray: Ray::new(hit_record.point, reflected),
-            attenuation: self.albedo,
+            attenuation,
         })
     }
 }
 
 pub struct Dielectric {
     ir: f64,
+    // optional coating whose interference replaces the Fresnel term
+    film: Option<ThinFilm>,
 }
 
 impl Dielectric {
     pub fn new(index_of_refraction: f64) -> Self {
         Self {
-            ir: index_of_refraction 
+            ir: index_of_refraction,
+            film: None,
+        }
+    }
+
+    // note: a soap bubble is a film over a dielectric with an
+    // index_of_refraction of 1.0, so rays pass straight through
+    pub fn with_film(index_of_refraction: f64, film: ThinFilm) -> Self {
+        Self {
+            ir: index_of_refraction,
+            film: Some(film),
         }
     }
 
     // note: Shlick's approximation takes in the *relative* ratio of 
     // refraction instead of the absolute index
-    fn reflectance(cosine: f64, ref_index: f64) -> f64 {
+    pub fn reflectance(cosine: f64, ref_index: f64) -> f64 {
         let r0 = ((1.0 - ref_index) / (1.0 + ref_index)).powf(2.0);
         r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
     }
@@ -90,7 +215,12 @@ impl Dielectric {
 
 impl Material for Dielectric {
 
-    fn scatter(&self, ray: Ray, hit_record: &HitRecord) -> Option<ScatteredRay> {
+    fn scatter(
+        &self,
+        ray: Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatteredRay> {
         // when the ray intersects on the front face, the ratio will be
         // that of outside air (1.0) to the material (self.ir)
         let unit_direction = ray.direction.unit();
@@ -108,7 +238,17 @@ impl Material for Dielectric {
         // with probability given by Shlick's approximation
         let cannot_refract = sin_theta * refraction_ratio > 1.0;
 
-        let direction = if cannot_refract || (Dielectric::reflectance(cos_theta, refraction_ratio) > rand::random()) {
+        if let (Some(film), false) = (&self.film, cannot_refract) {
+            return Some(self.scatter_with_film(
+                film,
+                unit_direction,
+                cos_theta,
+                hit_record,
+                sampler,
+            ));
+        }
+
+        let direction = if cannot_refract || (Dielectric::reflectance(cos_theta, refraction_ratio) > sampler.get_1d()) {
             reflect(unit_direction, hit_record.normal)
         } else {
             refract(unit_direction, hit_record.normal, refraction_ratio)
@@ -120,3 +260,564 @@ impl Material for Dielectric {
         })
     }
 }
+
+impl Dielectric {
+    // the film reflectance differs per color channel, so reflect with the
+    // average probability and reweight each channel by reflectance / p
+    fn scatter_with_film(
+        &self,
+        film: &ThinFilm,
+        unit_direction: Vec3,
+        cos_theta: f64,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> ScatteredRay {
+        let (outside, inside) = if hit_record.front_face {
+            (1.0, self.ir)
+        } else {
+            (self.ir, 1.0)
+        };
+        let reflectance = film.reflectance(
+            hit_record,
+            cos_theta,
+            outside,
+            Substrate::Dielectric(inside),
+        );
+        let p = ((reflectance.x + reflectance.y + reflectance.z) / 3.0).clamp(1e-4, 1.0 - 1e-4);
+
+        if p > sampler.get_1d() {
+            ScatteredRay {
+                ray: Ray::new(hit_record.point, reflect(unit_direction, hit_record.normal)),
+                attenuation: reflectance / p,
+            }
+        } else {
+            // the film faces are parallel, so the ray leaves the film
+            // bent as if it went straight from outside to inside
+            let transmittance = Color::new(1.0, 1.0, 1.0) - reflectance;
+            ScatteredRay {
+                ray: Ray::new(
+                    hit_record.point,
+                    refract(unit_direction, hit_record.normal, outside / inside),
+                ),
+                attenuation: transmittance / (1.0 - p),
+            }
+        }
+    }
+}
+
+// what is underneath a thin film
+enum Substrate {
+    // a transparent material with the given index of refraction
+    Dielectric(f64),
+    // a metal reflecting the given fraction of light per color channel
+    Conductor(Color),
+}
+
+// a thin transparent coating (soap, oil, anti-reflection layers) on top of
+// a surface. light reflected off the top and bottom of the film interferes,
+// so the reflectance depends on the wavelength and gives iridescent colors
+pub struct ThinFilm {
+    // thickness in nanometers (averaged over the color channels)
+    thickness: Arc<dyn Texture>,
+    ir: f64,
+}
+
+impl ThinFilm {
+    // representative wavelengths (nm) for the red, green and blue channels
+    const WAVELENGTHS: [f64; 3] = [650.0, 532.0, 450.0];
+
+    pub fn new(thickness: f64, index_of_refraction: f64) -> Self {
+        Self::from_texture(
+            Arc::new(SolidColor::new(Color::new(thickness, thickness, thickness))),
+            index_of_refraction,
+        )
+    }
+
+    pub fn from_texture(thickness: Arc<dyn Texture>, index_of_refraction: f64) -> Self {
+        Self {
+            thickness,
+            ir: index_of_refraction,
+        }
+    }
+
+    // reflectance of unpolarized light hitting the film from a medium with
+    // index `outside` at an incident angle with cosine `cos_theta`
+    fn reflectance(
+        &self,
+        hit_record: &HitRecord,
+        cos_theta: f64,
+        outside: f64,
+        substrate: Substrate,
+    ) -> Color {
+        let thickness = self
+            .thickness
+            .value(hit_record.u, hit_record.v, hit_record.point);
+        let thickness = ((thickness.x + thickness.y + thickness.z) / 3.0).max(0.0);
+
+        // Snell's law into the film; beyond the critical angle the film
+        // reflects everything at its top face
+        let cos_i = cos_theta.clamp(0.0, 1.0);
+        let sin_film = outside / self.ir * (1.0 - cos_i * cos_i).sqrt();
+        if sin_film >= 1.0 {
+            return Color::new(1.0, 1.0, 1.0);
+        }
+        let cos_film = (1.0 - sin_film * sin_film).sqrt();
+
+        // amplitude reflection coefficients (s and p polarized) at the top
+        let (top_s, top_p) = fresnel_amplitudes(outside, self.ir, cos_i, cos_film);
+
+        let channel = |i: usize, albedo: f64| {
+            let (bottom_s, bottom_p) = match substrate {
+                Substrate::Dielectric(inside) => {
+                    let sin_inside = self.ir / inside * sin_film;
+                    if sin_inside >= 1.0 {
+                        (1.0, 1.0)
+                    } else {
+                        let cos_inside = (1.0 - sin_inside * sin_inside).sqrt();
+                        fresnel_amplitudes(self.ir, inside, cos_film, cos_inside)
+                    }
+                }
+                // approximate the metal as a reflector with a phase flip
+                Substrate::Conductor(_) => (-albedo.sqrt(), -albedo.sqrt()),
+            };
+
+            // phase difference between the two reflected waves
+            let delta = 4.0 * std::f64::consts::PI * self.ir * thickness * cos_film
+                / ThinFilm::WAVELENGTHS[i];
+            (airy(top_s, bottom_s, delta) + airy(top_p, bottom_p, delta)) / 2.0
+        };
+
+        let albedo = match substrate {
+            Substrate::Conductor(albedo) => albedo,
+            Substrate::Dielectric(_) => Color::new(1.0, 1.0, 1.0),
+        };
+        Color::new(
+            channel(0, albedo.x),
+            channel(1, albedo.y),
+            channel(2, albedo.z),
+        )
+    }
+}
+
+// Fresnel amplitude coefficients (s, p) for light going from index n1 to
+// n2, given the cosines of the incident and transmitted angles
+fn fresnel_amplitudes(n1: f64, n2: f64, cos_i: f64, cos_t: f64) -> (f64, f64) {
+    let s = (n1 * cos_i - n2 * cos_t) / (n1 * cos_i + n2 * cos_t);
+    let p = (n2 * cos_i - n1 * cos_t) / (n2 * cos_i + n1 * cos_t);
+    (s, p)
+}
+
+// total reflectance of a film from the (real) amplitude coefficients of its
+// two faces, summing all of the internal reflections (Airy's formula)
+fn airy(r12: f64, r23: f64, delta: f64) -> f64 {
+    let cross = 2.0 * r12 * r23 * delta.cos();
+    ((r12 * r12 + r23 * r23 + cross) / (1.0 + r12 * r12 * r23 * r23 + cross)).clamp(0.0, 1.0)
+}
+
+// scatters light uniformly in all directions, used as the phase function
+// of participating media
+pub struct Isotropic {
+    albedo: Color,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(
+        &self,
+        _ray: Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatteredRay> {
+        Some(ScatteredRay {
+            ray: Ray::new(hit_record.point, sample_unit_vector(sampler.get_2d())),
+            attenuation: self.albedo,
+        })
+    }
+}
+
+// a blend of two materials. each scattering event picks `second` with
+// probability given by the mask (averaged over the color channels) and
+// `first` otherwise, which on average mixes the two surface responses
+pub struct MixMaterial {
+    first: Arc<dyn Material>,
+    second: Arc<dyn Material>,
+    mask: Arc<dyn Texture>,
+}
+
+impl MixMaterial {
+    // mix the materials by a constant amount in [0, 1]
+    pub fn new(first: Arc<dyn Material>, second: Arc<dyn Material>, amount: f64) -> Self {
+        let amount = amount.clamp(0.0, 1.0);
+        Self::from_mask(
+            first,
+            second,
+            Arc::new(SolidColor::new(Color::new(amount, amount, amount))),
+        )
+    }
+
+    pub fn from_mask(
+        first: Arc<dyn Material>,
+        second: Arc<dyn Material>,
+        mask: Arc<dyn Texture>,
+    ) -> Self {
+        Self {
+            first,
+            second,
+            mask,
+        }
+    }
+}
+
+impl Material for MixMaterial {
+    fn scatter(
+        &self,
+        ray: Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatteredRay> {
+        let mask = self
+            .mask
+            .value(hit_record.u, hit_record.v, hit_record.point);
+        let amount = ((mask.x + mask.y + mask.z) / 3.0).clamp(0.0, 1.0);
+
+        if amount > sampler.get_1d() {
+            self.second.scatter(ray, hit_record, sampler)
+        } else {
+            self.first.scatter(ray, hit_record, sampler)
+        }
+    }
+}
+
+// a clear dielectric coat (e.g. varnish or lacquer) over a base material.
+// the coat mirror reflects the Fresnel fraction of the incoming light and
+// the rest is refracted into the coat and scatters off the base material.
+// choosing between the two with the Fresnel probability keeps the energy
+// split unbiased. light leaving the base loses the Fresnel fraction at the
+// coat again on its way out; the coat is thin, so the exit direction is
+// not bent and internally reflected light is treated as absorbed
+pub struct Coated {
+    base: Arc<dyn Material>,
+    ir: f64,
+}
+
+impl Coated {
+    pub fn new(base: Arc<dyn Material>, index_of_refraction: f64) -> Self {
+        Self {
+            base,
+            ir: index_of_refraction,
+        }
+    }
+}
+
+impl Material for Coated {
+    fn scatter(
+        &self,
+        ray: Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatteredRay> {
+        // the coat only sits on the outside of the surface
+        if !hit_record.front_face {
+            return self.base.scatter(ray, hit_record, sampler);
+        }
+
+        let unit_direction = ray.direction.unit();
+        let cos_theta = Vec3::dot(unit_direction, -hit_record.normal).min(1.0);
+
+        if Dielectric::reflectance(cos_theta, 1.0 / self.ir) > sampler.get_1d() {
+            Some(ScatteredRay {
+                ray: Ray::new(hit_record.point, reflect(unit_direction, hit_record.normal)),
+                attenuation: Color::new(1.0, 1.0, 1.0),
+            })
+        } else {
+            let refracted = refract(unit_direction, hit_record.normal, 1.0 / self.ir);
+            let mut scattered =
+                self.base
+                    .scatter(Ray::new(ray.orig, refracted), hit_record, sampler)?;
+
+            let cos_exit = Vec3::dot(scattered.ray.direction.unit(), hit_record.normal);
+            if cos_exit > 0.0 {
+                let transmittance = 1.0 - Dielectric::reflectance(cos_exit.min(1.0), self.ir);
+                scattered.attenuation = transmittance * scattered.attenuation;
+            }
+            Some(scattered)
+        }
+    }
+}
+
+// perturbs the shading normal of a base material with a tangent-space
+// normal map, whose red/green/blue channels in [0, 1] encode the components
+// along dp/du, dp/dv and the surface normal in [-1, 1]
+pub struct NormalMapped {
+    base: Arc<dyn Material>,
+    map: Arc<dyn Texture>,
+}
+
+impl NormalMapped {
+    pub fn new(base: Arc<dyn Material>, map: Arc<dyn Texture>) -> Self {
+        Self { base, map }
+    }
+}
+
+impl Material for NormalMapped {
+    fn scatter(
+        &self,
+        ray: Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatteredRay> {
+        let Some((tangent, bitangent, normal)) = tangent_frame(hit_record) else {
+            return self.base.scatter(ray, hit_record, sampler);
+        };
+
+        let texel = self.map.value(hit_record.u, hit_record.v, hit_record.point);
+        let shading_normal = (2.0 * texel.x - 1.0) * tangent
+            + (2.0 * texel.y - 1.0) * bitangent
+            + (2.0 * texel.z - 1.0) * normal;
+        if shading_normal.near_zero() {
+            return self.base.scatter(ray, hit_record, sampler);
+        }
+
+        scatter_with_shading_normal(
+            self.base.as_ref(),
+            ray,
+            hit_record,
+            shading_normal.unit(),
+            sampler,
+        )
+    }
+}
+
+// perturbs the shading normal of a base material as if the surface were
+// displaced along its normal by a grayscale height map. `scale` is the
+// displacement in world units for a height of 1.0
+pub struct BumpMapped {
+    base: Arc<dyn Material>,
+    height: Arc<dyn Texture>,
+    scale: f64,
+}
+
+impl BumpMapped {
+    pub fn new(base: Arc<dyn Material>, height: Arc<dyn Texture>, scale: f64) -> Self {
+        Self {
+            base,
+            height,
+            scale,
+        }
+    }
+}
+
+impl Material for BumpMapped {
+    fn scatter(
+        &self,
+        ray: Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatteredRay> {
+        let Some((_, _, normal)) = tangent_frame(hit_record) else {
+            return self.base.scatter(ray, hit_record, sampler);
+        };
+
+        // finite differences of the height in (u, v), evaluated at the
+        // matching offset points on the surface
+        let delta = 0.0005;
+        let height = |du: f64, dv: f64| {
+            let point = hit_record.point + du * hit_record.dpdu + dv * hit_record.dpdv;
+            let h = self
+                .height
+                .value(hit_record.u + du, hit_record.v + dv, point);
+            self.scale * (h.x + h.y + h.z) / 3.0
+        };
+        let h = height(0.0, 0.0);
+        let dhdu = (height(delta, 0.0) - h) / delta;
+        let dhdv = (height(0.0, delta) - h) / delta;
+
+        // tangents of the displaced surface p + h(u, v) * n
+        let dpdu = hit_record.dpdu + dhdu * normal;
+        let dpdv = hit_record.dpdv + dhdv * normal;
+        let mut shading_normal = Vec3::cross(dpdu, dpdv);
+        if shading_normal.near_zero() {
+            return self.base.scatter(ray, hit_record, sampler);
+        }
+        shading_normal = shading_normal.unit();
+        if Vec3::dot(shading_normal, normal) < 0.0 {
+            shading_normal = -shading_normal;
+        }
+
+        scatter_with_shading_normal(self.base.as_ref(), ray, hit_record, shading_normal, sampler)
+    }
+}
+
+// an orthonormal (tangent, bitangent, outward normal) frame at the hit,
+// or None if the hittable did not record tangent vectors
+fn tangent_frame(hit_record: &HitRecord) -> Option<(Vec3, Vec3, Vec3)> {
+    let normal = if hit_record.front_face {
+        hit_record.normal
+    } else {
+        -hit_record.normal
+    };
+
+    let tangent = hit_record.dpdu - Vec3::dot(hit_record.dpdu, normal) * normal;
+    if tangent.near_zero() {
+        return None;
+    }
+    let tangent = tangent.unit();
+
+    // build the bitangent from the normal so it is well defined even where
+    // dp/dv is degenerate (at the poles), but keep dp/dv's orientation
+    let mut bitangent = Vec3::cross(normal, tangent);
+    if Vec3::dot(bitangent, hit_record.dpdv) < 0.0 {
+        bitangent = -bitangent;
+    }
+    Some((tangent, bitangent, normal))
+}
+
+// scatter off the base material as if the surface had the given (outward)
+// shading normal, without letting light leak through the real geometry
+fn scatter_with_shading_normal(
+    base: &dyn Material,
+    ray: Ray,
+    hit_record: &HitRecord,
+    outward_shading_normal: Vec3,
+    sampler: &mut dyn Sampler,
+) -> Option<ScatteredRay> {
+    let geometric_normal = hit_record.normal;
+    let mut normal = if hit_record.front_face {
+        outward_shading_normal
+    } else {
+        -outward_shading_normal
+    };
+
+    // a shading normal that faces away from the incoming ray would have
+    // the surface lit from behind, so fall back to the geometric normal
+    if Vec3::dot(normal, ray.direction) >= 0.0 {
+        normal = geometric_normal;
+    }
+
+    let mut shading_record = hit_record.clone();
+    shading_record.normal = normal;
+    let scattered = base.scatter(ray, &shading_record, sampler)?;
+
+    // a ray that is on one side of the shading surface but on the other
+    // side of the geometric surface would pass through the object (or be
+    // trapped inside it), so absorb it instead
+    let direction = scattered.ray.direction;
+    let shading_side = Vec3::dot(direction, normal) > 0.0;
+    let geometric_side = Vec3::dot(direction, geometric_normal) > 0.0;
+    if shading_side != geometric_side {
+        return None;
+    }
+
+    Some(scattered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::IndependentSampler;
+
+    // a head-on hit of the top of a flat surface at the origin
+    fn hit(material: Arc<dyn Material>) -> (Ray, HitRecord) {
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit_record = HitRecord::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            ray,
+            1.0,
+            material,
+        );
+        (ray, hit_record)
+    }
+
+    #[test]
+    fn mix_mask_of_zero_or_one_always_picks_one_material() {
+        let red: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(1.0, 0.0, 0.0)));
+        let blue: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.0, 0.0, 1.0)));
+        let mut sampler = IndependentSampler::new(3);
+
+        for (amount, expected) in [
+            (0.0, Color::new(1.0, 0.0, 0.0)),
+            (1.0, Color::new(0.0, 0.0, 1.0)),
+        ] {
+            let mix: Arc<dyn Material> =
+                Arc::new(MixMaterial::new(red.clone(), blue.clone(), amount));
+            let (ray, hit_record) = hit(mix.clone());
+            for index in 0..256 {
+                sampler.start_pixel_sample((0, 0), index);
+                let scattered = mix.scatter(ray, &hit_record, &mut sampler).unwrap();
+                assert_eq!(scattered.attenuation, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn coat_reflects_or_attenuates_the_base_on_exit() {
+        let albedo = Color::new(0.8, 0.8, 0.8);
+        let coated: Arc<dyn Material> =
+            Arc::new(Coated::new(Arc::new(Lambertian::new(albedo)), 1.5));
+        let (ray, hit_record) = hit(coated.clone());
+        let mut sampler = IndependentSampler::new(5);
+
+        let (mut reflected, mut transmitted) = (0, 0);
+        for index in 0..1024 {
+            sampler.start_pixel_sample((0, 0), index);
+            let scattered = coated.scatter(ray, &hit_record, &mut sampler).unwrap();
+            if scattered.attenuation == Color::new(1.0, 1.0, 1.0) {
+                // a mirror bounce straight back off the coat
+                assert!((scattered.ray.direction.unit().y - 1.0).abs() < 1e-9);
+                reflected += 1;
+            } else {
+                // the base response less the coat's Fresnel loss on the way
+                // out, which is at least the head-on 4% of glass
+                let expected_max = albedo.x * (1.0 - Dielectric::reflectance(1.0, 1.5));
+                assert!(scattered.attenuation.x <= expected_max + 1e-9);
+                assert!(scattered.attenuation.x > 0.0);
+                transmitted += 1;
+            }
+        }
+
+        // head-on glass reflects about 4% of the light
+        assert!(reflected > 10 && reflected < 90, "{}", reflected);
+        assert_eq!(reflected + transmitted, 1024);
+    }
+
+    // reflectance of a film on glass for light hitting it head-on
+    fn film_on_glass(thickness: f64) -> Color {
+        let (_, hit_record) = hit(Arc::new(Lambertian::new(Color::new(1.0, 1.0, 1.0))));
+        ThinFilm::new(thickness, 1.38).reflectance(
+            &hit_record,
+            1.0,
+            1.0,
+            Substrate::Dielectric(1.5),
+        )
+    }
+
+    #[test]
+    fn film_of_zero_thickness_is_the_bare_interface() {
+        // normal incidence Fresnel reflectance from air into glass
+        let bare = ((1.0 - 1.5) / (1.0 + 1.5_f64)).powi(2);
+        let reflectance = film_on_glass(0.0);
+        for channel in [reflectance.x, reflectance.y, reflectance.z] {
+            assert!((channel - bare).abs() < 1e-12, "{}", channel);
+        }
+    }
+
+    #[test]
+    fn quarter_wave_film_is_darkest_at_its_design_wavelength() {
+        // a quarter of the green wavelength inside the film
+        let quarter_wave = ThinFilm::WAVELENGTHS[1] / (4.0 * 1.38);
+        let green = film_on_glass(quarter_wave).y;
+
+        // the two reflections cancel as far as the indices allow
+        let expected = ((1.5 - 1.38 * 1.38) / (1.5 + 1.38 * 1.38_f64)).powi(2);
+        assert!((green - expected).abs() < 1e-12, "{}", green);
+        for offset in [-20.0, -5.0, 5.0, 20.0] {
+            assert!(film_on_glass(quarter_wave + offset).y > green);
+        }
+    }
+}
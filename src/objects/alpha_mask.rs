@@ -0,0 +1,133 @@
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::sampler;
+use crate::texture::Texture;
+use std::sync::Arc;
+
+// how the opacity texture decides whether a hit is kept
+pub enum AlphaMode {
+    // keep hits whose alpha is at least the threshold (hard cutouts)
+    Cutout(f64),
+    // keep hits with probability alpha (soft, semi-transparent edges)
+    Stochastic,
+}
+
+// an object with holes in it (leaves, fences, decals). intersections where
+// the opacity texture is transparent are skipped and the ray continues to
+// whatever lies behind, so the holes apply to every ray that is traced
+pub struct AlphaMask {
+    object: Arc<dyn Hittable>,
+    // opacity in [0, 1], averaged over the color channels
+    alpha: Arc<dyn Texture>,
+    mode: AlphaMode,
+}
+
+impl AlphaMask {
+    pub fn new(object: Arc<dyn Hittable>, alpha: Arc<dyn Texture>, mode: AlphaMode) -> Self {
+        Self {
+            object,
+            alpha,
+            mode,
+        }
+    }
+}
+
+impl Hittable for AlphaMask {
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord> {
+        let mut ray_t = ray_t;
+        loop {
+            let hit_record = self.object.hit(ray, ray_t)?;
+
+            let alpha = self
+                .alpha
+                .value(hit_record.u, hit_record.v, hit_record.point);
+            let alpha = (alpha.x + alpha.y + alpha.z) / 3.0;
+            let opaque = match self.mode {
+                AlphaMode::Cutout(threshold) => alpha >= threshold,
+                // Hittable::hit doesn't get the path's sampler (lists, masks
+                // and media are queried by every kind of ray), so this draws
+                // from the thread's generator, which the camera reseeds for
+                // each pixel sample to keep renders reproducible
+                AlphaMode::Stochastic => alpha > sampler::random(),
+            };
+            if opaque {
+                return Some(hit_record);
+            }
+
+            // look for the next intersection past the transparent one
+            ray_t = Interval::new(hit_record.t, ray_t.max);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use crate::objects::{HittableList, Sphere};
+    use crate::texture::SolidColor;
+    use crate::vec3::{Point, Vec3};
+
+    // opaque above the xz plane and transparent below it
+    struct TopHalf;
+
+    impl Texture for TopHalf {
+        fn value(&self, _u: f64, _v: f64, p: Point) -> Color {
+            let alpha = if p.y > 0.0 { 1.0 } else { 0.0 };
+            Color::new(alpha, alpha, alpha)
+        }
+    }
+
+    fn sphere(center: Point) -> Arc<dyn Hittable> {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        Arc::new(Sphere::new(center, 0.5, material))
+    }
+
+    fn toward_origin(y: f64) -> Ray {
+        Ray::new(Point::new(0.0, y, 5.0), Vec3::new(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn transparent_mask_shows_the_object_behind() {
+        let clear = Arc::new(SolidColor::new(Color::new(0.0, 0.0, 0.0)));
+        for mode in [AlphaMode::Cutout(0.5), AlphaMode::Stochastic] {
+            let mut world = HittableList::new();
+            world.add(Arc::new(AlphaMask::new(
+                sphere(Point::new(0.0, 0.0, 0.0)),
+                clear.clone(),
+                mode,
+            )));
+            world.add(sphere(Point::new(0.0, 0.0, -2.0)));
+
+            let hit = world
+                .hit(toward_origin(0.0), Interval::new(0.001, f64::INFINITY))
+                .unwrap();
+            // the front of the sphere behind, not either side of the mask
+            assert!((hit.t - 6.5).abs() < 1e-9, "{}", hit.t);
+        }
+    }
+
+    #[test]
+    fn cutout_never_hits_the_transparent_region() {
+        let mask = AlphaMask::new(
+            sphere(Point::new(0.0, 0.0, 0.0)),
+            Arc::new(TopHalf),
+            AlphaMode::Cutout(0.5),
+        );
+        for k in 0..=40 {
+            let y = -0.45 + 0.9 * k as f64 / 40.0;
+            let hit = mask.hit(toward_origin(y), Interval::new(0.001, f64::INFINITY));
+            match hit {
+                Some(hit) => assert!(hit.point.y > 0.0, "{:?}", hit.point),
+                None => assert!(y <= 0.0, "{}", y),
+            }
+        }
+
+        // a ray entering through the bottom half hits the top on the way out
+        let ray = Ray::new(Point::new(0.0, -1.0, 5.0), Vec3::new(0.0, 0.2, -1.0));
+        let hit = mask.hit(ray, Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert!(hit.point.y > 0.0 && hit.point.z < 0.0, "{:?}", hit.point);
+    }
+}
@@ -31,8 +31,7 @@ impl Hittable for HittableList {
         // hit on the entire original interval
         self.objects
             .iter()
-            .map(|object| object.hit(ray, ray_t))
-            .filter_map(|hit_record| hit_record)
+            .filter_map(|object| object.hit(ray, ray_t))
             .min_by(|h1, h2| h1.t.partial_cmp(&h2.t).unwrap())
     }
 }
@@ -1,5 +1,9 @@
+pub mod alpha_mask;
 pub mod sphere;
 pub mod hittable_list;
+pub mod subsurface;
 
+pub use alpha_mask::{AlphaMask, AlphaMode};
 pub use sphere::Sphere;
-pub use hittable_list::HittableList;
\ No newline at end of file
+pub use hittable_list::HittableList;
+pub use subsurface::Subsurface;
\ No newline at end of file
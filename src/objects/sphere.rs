@@ -2,6 +2,7 @@ use crate::hittable::{HitRecord, Hittable};
 use crate::interval::Interval;
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::stats;
 use crate::vec3::{Point, Vec3};
 use std::sync::Arc;
 
@@ -15,10 +16,34 @@ impl Sphere {
     pub fn new(center: Point, radius: f64, material: Arc<dyn Material>) -> Self {
         Self { center, radius, material }
     }
+
+    // map a point on the unit sphere to (u, v) in [0, 1]^2, where u is the
+    // angle around the y-axis (from x = -1) and v is the angle from y = -1
+    fn get_sphere_uv(p: Point) -> (f64, f64) {
+        let theta = (-p.y).acos();
+        let phi = (-p.z).atan2(p.x) + std::f64::consts::PI;
+        (
+            phi / (2.0 * std::f64::consts::PI),
+            theta / std::f64::consts::PI,
+        )
+    }
+
+    // tangent vectors (dp/du, dp/dv) at the point p on the unit sphere
+    // for the parameterization in get_sphere_uv, scaled by the radius
+    fn get_sphere_tangents(p: Point, radius: f64) -> (Vec3, Vec3) {
+        let pi = std::f64::consts::PI;
+        // clamp sin(theta) away from zero at the poles, where u is degenerate
+        let sin_theta = (1.0 - p.y * p.y).sqrt().max(1e-8);
+        let dpdu = 2.0 * pi * radius * Vec3::new(p.z, 0.0, -p.x);
+        let dpdv =
+            pi * radius * Vec3::new(-p.x * p.y / sin_theta, sin_theta, -p.z * p.y / sin_theta);
+        (dpdu, dpdv)
+    }
 }
 
 impl Hittable for Sphere {
     fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord> {
+        stats::count(|counters| counters.intersection_tests += 1);
         let oc = ray.orig - self.center;
         let a = ray.direction.length().powf(2.0);
         let b = Vec3::dot(ray.direction, oc);
@@ -43,6 +68,39 @@ impl Hittable for Sphere {
 
         let point = ray.at(t);
         let outward_normal = (point - self.center) / self.radius;
-        Some(HitRecord::new(point, outward_normal, ray, t, Arc::clone(&self.material)))
+        let mut hit_record =
+            HitRecord::new(point, outward_normal, ray, t, Arc::clone(&self.material));
+        // a negative radius flips the normal (for hollow spheres), so take the
+        // uv from the geometric direction instead
+        let direction = (point - self.center).unit();
+        (hit_record.u, hit_record.v) = Sphere::get_sphere_uv(direction);
+        (hit_record.dpdu, hit_record.dpdv) =
+            Sphere::get_sphere_tangents(direction, self.radius.abs());
+        Some(hit_record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tangents_follow_uv() {
+        // stepping along dp/du and dp/dv should match re-evaluating the
+        // parameterization at (u + du, v + dv)
+        let radius = 2.0;
+        let p = Vec3::new(0.3, -0.5, 0.6).unit();
+        let (u, v) = Sphere::get_sphere_uv(p);
+        let (dpdu, dpdv) = Sphere::get_sphere_tangents(p, radius);
+
+        let step = 1e-6;
+        let (u2, _) = Sphere::get_sphere_uv((p + step * dpdu / radius).unit());
+        let (_, v2) = Sphere::get_sphere_uv((p + step * dpdv / radius).unit());
+        assert!(((u2 - u) / step - 1.0).abs() < 1e-3);
+        assert!(((v2 - v) / step - 1.0).abs() < 1e-3);
+
+        // and both lie in the tangent plane
+        assert!(Vec3::dot(dpdu, p).abs() < 1e-9);
+        assert!(Vec3::dot(dpdv, p).abs() < 1e-9);
     }
 }
@@ -0,0 +1,68 @@
+use crate::color::Color;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::{Isotropic, Material};
+use crate::ray::Ray;
+use crate::sampler;
+use std::sync::Arc;
+
+// a translucent object (skin, wax, marble) filled with a scattering medium.
+// the boundary is any closed hittable whose own material handles the
+// surface (usually a Dielectric), and rays that refract inside random walk
+// through the medium until they leave through the boundary again
+pub struct Subsurface {
+    boundary: Arc<dyn Hittable>,
+    // average distance a ray travels inside before scattering
+    mean_free_path: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl Subsurface {
+    pub fn new(boundary: Arc<dyn Hittable>, albedo: Color, mean_free_path: f64) -> Self {
+        Self {
+            boundary,
+            mean_free_path,
+            phase_function: Arc::new(Isotropic::new(albedo)),
+        }
+    }
+}
+
+impl Hittable for Subsurface {
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord> {
+        // look past ray_t.max so we know whether the ray starts inside,
+        // even when the boundary is farther than the closest hit so far
+        let boundary_hit = self
+            .boundary
+            .hit(ray, Interval::new(ray_t.min, f64::INFINITY))?;
+
+        // hitting the outside of the boundary means the ray starts outside,
+        // so let the surface material reflect/refract it
+        if boundary_hit.front_face {
+            return ray_t.surrounds(boundary_hit.t).then_some(boundary_hit);
+        }
+
+        // otherwise the ray is travelling through the medium. sample the
+        // (exponentially distributed) distance to the next scattering event
+        let ray_length = ray.direction.length();
+        let distance_inside = (boundary_hit.t - ray_t.min) * ray_length;
+        let scatter_distance = -self.mean_free_path * sampler::random().ln();
+
+        if scatter_distance >= distance_inside {
+            return ray_t.surrounds(boundary_hit.t).then_some(boundary_hit);
+        }
+
+        let t = ray_t.min + scatter_distance / ray_length;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        // the normal is arbitrary for the isotropic phase function
+        Some(HitRecord::new(
+            ray.at(t),
+            -ray.direction.unit(),
+            ray,
+            t,
+            Arc::clone(&self.phase_function),
+        ))
+    }
+}
@@ -0,0 +1,189 @@
+use super::sobol::{owen_scramble, sobol, to_unit};
+use super::{hash, mix_bits, Pcg32, Sampler};
+use std::sync::OnceLock;
+
+// side length of the tileable blue noise mask
+const TILE: usize = 64;
+
+// a Sobol sequence shared by all pixels, shifted (Cranley-Patterson
+// rotation) by a blue noise mask across the image. neighboring pixels get
+// very different shifts, so their errors don't clump and the remaining
+// noise is high frequency, which looks like fine grain rather than
+// blotches at low sample counts
+pub struct BlueNoiseSampler {
+    seed: u64,
+    pixel: (i32, i32),
+    index: u32,
+    dimension: u32,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    // the shift of the pixel for one dimension. each dimension reads the
+    // mask at a different offset, so the dimensions stay uncorrelated
+    fn shift(&self, dimension: u32) -> f64 {
+        let offset = hash(self.seed, (0, 0), dimension);
+        let x = (self.pixel.1 as i64 + (offset & 0xffff) as i64).rem_euclid(TILE as i64);
+        let y = (self.pixel.0 as i64 + ((offset >> 16) & 0xffff) as i64).rem_euclid(TILE as i64);
+        blue_noise_tile()[y as usize * TILE + x as usize]
+    }
+
+    // the point of the shared sequence for the sample index, which every
+    // pixel sees with the same scrambling
+    fn point(&self, sobol_dimension: u32, dimension: u32) -> f64 {
+        let scramble = hash(self.seed, (0, 0), dimension) >> 32;
+        to_unit(owen_scramble(
+            sobol(self.index, sobol_dimension),
+            scramble as u32,
+        ))
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), index: u32) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn set_dimension(&mut self, dimension: u32) {
+        self.dimension = dimension;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        (self.point(0, dimension) + self.shift(dimension)).fract()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let dimension = self.dimension;
+        self.dimension += 2;
+        (
+            (self.point(0, dimension) + self.shift(dimension)).fract(),
+            (self.point(1, dimension) + self.shift(dimension + 1)).fract(),
+        )
+    }
+}
+
+// a tileable mask of values in [0, 1) whose neighbors are as different as
+// possible, made once with the void-and-cluster method (Ulichney 1993)
+fn blue_noise_tile() -> &'static [f64] {
+    static TILE_VALUES: OnceLock<Vec<f64>> = OnceLock::new();
+    TILE_VALUES.get_or_init(void_and_cluster)
+}
+
+fn void_and_cluster() -> Vec<f64> {
+    let n = TILE * TILE;
+
+    // gaussian falloff by wrapped distance, indexed by (dy, dx)
+    let sigma = 1.5;
+    let kernel: Vec<f64> = (0..n)
+        .map(|k| {
+            let wrap = |d: usize| d.min(TILE - d) as f64;
+            let (dy, dx) = (wrap(k / TILE), wrap(k % TILE));
+            (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp()
+        })
+        .collect();
+
+    // the energy of each position is how crowded its neighborhood is
+    let update = |energy: &mut [f64], k: usize, sign: f64| {
+        let (y, x) = (k / TILE, k % TILE);
+        for (other, e) in energy.iter_mut().enumerate() {
+            let dy = (other / TILE + TILE - y) % TILE;
+            let dx = (other % TILE + TILE - x) % TILE;
+            *e += sign * kernel[dy * TILE + dx];
+        }
+    };
+    // the most crowded set position, or the emptiest unset one
+    let tightest_cluster = |energy: &[f64], set: &[bool]| {
+        (0..n)
+            .filter(|&k| set[k])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+    let largest_void = |energy: &[f64], set: &[bool]| {
+        (0..n)
+            .filter(|&k| !set[k])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+
+    // start from a tenth of the positions set at random, then move points
+    // from clusters into voids until the pattern is evenly spread
+    let mut rng = Pcg32::new(mix_bits(TILE as u64), 0);
+    let mut set = vec![false; n];
+    let mut energy = vec![0.0; n];
+    let initial = n / 10;
+    let mut count = 0;
+    while count < initial {
+        let k = (rng.next_f64() * n as f64) as usize;
+        if !set[k] {
+            set[k] = true;
+            update(&mut energy, k, 1.0);
+            count += 1;
+        }
+    }
+    loop {
+        let cluster = tightest_cluster(&energy, &set);
+        set[cluster] = false;
+        update(&mut energy, cluster, -1.0);
+        let void = largest_void(&energy, &set);
+        set[void] = true;
+        update(&mut energy, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    // rank the initial points by removing clusters first, then rank the
+    // rest by filling the largest voids
+    let mut rank = vec![0; n];
+    let (mut prototype_set, mut prototype_energy) = (set.clone(), energy.clone());
+    for r in (0..initial).rev() {
+        let cluster = tightest_cluster(&prototype_energy, &prototype_set);
+        prototype_set[cluster] = false;
+        update(&mut prototype_energy, cluster, -1.0);
+        rank[cluster] = r;
+    }
+    for r in initial..n {
+        let void = largest_void(&energy, &set);
+        set[void] = true;
+        update(&mut energy, void, 1.0);
+        rank[void] = r;
+    }
+
+    rank.iter().map(|&r| (r as f64 + 0.5) / n as f64).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_is_blue_noise() {
+        let tile = blue_noise_tile();
+
+        // every value is used exactly once
+        let mut sorted = tile.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        for (k, value) in sorted.iter().enumerate() {
+            assert_eq!(*value, (k as f64 + 0.5) / (TILE * TILE) as f64);
+        }
+
+        // neighbors differ by more than the 1/3 of white noise on average
+        let difference = (0..TILE * TILE)
+            .map(|k| (tile[k] - tile[k / TILE * TILE + (k + 1) % TILE]).abs())
+            .sum::<f64>()
+            / (TILE * TILE) as f64;
+        assert!(difference > 0.4, "{}", difference);
+    }
+}
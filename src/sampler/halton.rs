@@ -0,0 +1,105 @@
+use super::{hash, mix_bits, permutation_element, Pcg32, Sampler, ONE_MINUS_EPSILON};
+
+// the first primes, one base per dimension. dimensions past the end (deep
+// bounces) get independent random numbers instead, since reusing a base
+// would correlate them with the earlier dimension that has it
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+// the Halton sequence, which is the radical inverse of the sample index in
+// a different prime base per dimension. each pixel scrambles the digits
+// with its own random permutations (Owen scrambling)
+pub struct HaltonSampler {
+    seed: u64,
+    pixel: (i32, i32),
+    index: u32,
+    dimension: u32,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), index: u32) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn set_dimension(&mut self, dimension: u32) {
+        self.dimension = dimension;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let seed = hash(self.seed, self.pixel, self.dimension);
+        let base = PRIMES.get(self.dimension as usize);
+        self.dimension += 1;
+        match base {
+            Some(&base) => owen_scrambled_radical_inverse(base, self.index as u64, seed),
+            None => Pcg32::new(seed, self.index as u64).next_f64(),
+        }
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+// the digits of a in the given base mirrored around the decimal point,
+// with each digit permuted depending on the digits that come before it
+fn owen_scrambled_radical_inverse(base: u64, mut a: u64, seed: u64) -> f64 {
+    // enough digits to fill the precision of an f64, including the
+    // leading zeros of a, which get scrambled too
+    let digits = (53.0 / (base as f64).log2()) as u32;
+
+    let mut reversed_digits: u64 = 0;
+    let mut inverse_base_m = 1.0;
+    for _ in 0..digits {
+        let next = a / base;
+        let digit = a - next * base;
+        let digit_seed = mix_bits(seed ^ reversed_digits) as u32;
+        let digit = permutation_element(digit as u32, base as u32, digit_seed) as u64;
+        reversed_digits = reversed_digits * base + digit;
+        inverse_base_m /= base as f64;
+        a = next;
+    }
+    (reversed_digits as f64 * inverse_base_m).min(ONE_MINUS_EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dimensions_past_the_primes_are_uncorrelated() {
+        let mut sampler = HaltonSampler::new(0);
+        let values: Vec<(f64, f64)> = (0..1024)
+            .map(|index| {
+                sampler.start_pixel_sample((3, 5), index);
+                sampler.set_dimension(2);
+                let first = sampler.get_1d();
+                sampler.set_dimension(2 + PRIMES.len() as u32);
+                (first, sampler.get_1d())
+            })
+            .collect();
+
+        let n = values.len() as f64;
+        let covariance = values
+            .iter()
+            .map(|(a, b)| (a - 0.5) * (b - 0.5))
+            .sum::<f64>()
+            / n;
+        // the correlation of two uniform variables is covariance * 12
+        assert!((12.0 * covariance).abs() < 0.1, "{}", 12.0 * covariance);
+    }
+}
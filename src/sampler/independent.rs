@@ -0,0 +1,33 @@
+use super::{sample_stream, Pcg32, Sampler};
+
+// uniform random numbers with no relation between samples, which is what
+// the renderer used before samplers. each pixel sample has its own stream
+pub struct IndependentSampler {
+    seed: u64,
+    rng: Pcg32,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: Pcg32::new(seed, 0),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), index: u32) {
+        self.rng = Pcg32::new(self.seed, sample_stream(pixel, index));
+    }
+
+    fn set_dimension(&mut self, _dimension: u32) {}
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.next_f64()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.rng.next_f64(), self.rng.next_f64())
+    }
+}
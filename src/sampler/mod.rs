@@ -0,0 +1,183 @@
+mod blue_noise;
+mod halton;
+mod independent;
+mod pcg;
+mod sobol;
+mod stratified;
+
+pub use blue_noise::BlueNoiseSampler;
+pub use halton::HaltonSampler;
+pub use independent::IndependentSampler;
+pub use pcg::{random, seed_thread_rng, Pcg32, ThreadSampler};
+pub use sobol::SobolSampler;
+pub use stratified::StratifiedSampler;
+
+// the largest f64 below 1.0, so that samples stay in [0, 1)
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+// draws the random numbers for the samples of a pixel. each sample is a
+// point in a high-dimensional cube, where every decision along the path
+// (pixel position, lens position, each bounce) gets its own dimensions.
+// well-spread points in that cube give less noise than independent ones.
+//
+// note: decisions made while intersecting (stochastic alpha, subsurface
+// free paths) don't see the sampler and stay independent
+pub trait Sampler {
+    // start drawing the sample with the given index for pixel (i, j),
+    // beginning at dimension 0
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), index: u32);
+
+    // skip to the given dimension, so that a decision always draws the
+    // same dimensions no matter how many were used before it
+    fn set_dimension(&mut self, dimension: u32);
+
+    fn get_1d(&mut self) -> f64;
+
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+// which sampler the camera renders with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplerKind {
+    // independent uniform random numbers
+    Independent,
+    // jittered strata per dimension, shuffled between dimensions
+    Stratified,
+    // the Halton sequence with Owen scrambled digits
+    Halton,
+    // pairs of dimensions from the Sobol sequence with Owen scrambling
+    Sobol,
+    // a Sobol sequence shifted per pixel by a blue noise mask, which
+    // spreads the error evenly across the image at low sample counts
+    BlueNoise,
+}
+
+impl SamplerKind {
+    // a new sampler for pixels with the given number of samples. the same
+    // seed always gives the same samples
+    pub fn create(&self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(samples_per_pixel, seed)),
+            SamplerKind::BlueNoise => Box::new(BlueNoiseSampler::new(seed)),
+        }
+    }
+}
+
+// scrambles the bits of v so that nearby inputs give unrelated outputs
+fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5d329728ea185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81dadef4bc2dd44d);
+    v ^= v >> 33;
+    v
+}
+
+// a hash of the seed, pixel and dimension, used to randomize each pixel's
+// points differently so the pattern doesn't repeat across the image
+fn hash(seed: u64, pixel: (i32, i32), dimension: u32) -> u64 {
+    let key = ((pixel.0 as u32 as u64) << 32) | pixel.1 as u32 as u64;
+    mix_bits(mix_bits(mix_bits(key) ^ seed) ^ dimension as u64)
+}
+
+// the random number stream of one sample of a pixel. streams depend only
+// on where the sample is, not on which thread renders it or when
+pub fn sample_stream(pixel: (i32, i32), index: u32) -> u64 {
+    let key = ((pixel.0 as u32 as u64) << 32) | pixel.1 as u32 as u64;
+    mix_bits(mix_bits(key) ^ index as u64)
+}
+
+// element i of a random permutation of 0..n chosen by p, without building
+// the permutation (Kensler, "Correlated Multi-Jittered Sampling")
+fn permutation_element(mut i: u32, n: u32, p: u32) -> u32 {
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the error of estimating the integral of a smooth function over the
+    // unit square, averaged over many pixels
+    fn mean_error(kind: SamplerKind, samples: u32) -> f64 {
+        let f = |x: f64, y: f64| (3.0 * x).sin() * (2.0 * y).cos() + x * y;
+        let exact = (1.0 - 3.0_f64.cos()) / 3.0 * 2.0_f64.sin() / 2.0 + 0.25;
+
+        let mut sampler = kind.create(samples, 0);
+        let pixels = 64;
+        let mut error = 0.0;
+        for pixel in 0..pixels {
+            let mut sum = 0.0;
+            for index in 0..samples {
+                sampler.start_pixel_sample((pixel, 0), index);
+                // the lens dimensions, past the pixel position
+                sampler.set_dimension(2);
+                let (x, y) = sampler.get_2d();
+                sum += f(x, y);
+            }
+            error += (sum / samples as f64 - exact).abs();
+        }
+        error / pixels as f64
+    }
+
+    #[test]
+    fn low_discrepancy_beats_independent() {
+        let independent = mean_error(SamplerKind::Independent, 64);
+        for kind in [
+            SamplerKind::Stratified,
+            SamplerKind::Halton,
+            SamplerKind::Sobol,
+            SamplerKind::BlueNoise,
+        ] {
+            let error = mean_error(kind, 64);
+            assert!(
+                error < independent / 2.0,
+                "{:?}: {} vs {}",
+                kind,
+                error,
+                independent
+            );
+        }
+    }
+
+    #[test]
+    fn permutation_is_a_permutation() {
+        let mut seen: Vec<u32> = (0..37)
+            .map(|i| permutation_element(i, 37, 0xdeadbeef))
+            .collect();
+        seen.sort();
+        assert_eq!(seen, (0..37).collect::<Vec<u32>>());
+    }
+}
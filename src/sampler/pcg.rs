@@ -0,0 +1,102 @@
+use super::Sampler;
+use std::cell::RefCell;
+
+// the PCG32 random number generator (O'Neill, pcg-random.org). it is small
+// and fast, and every (seed, stream) pair gives an independent sequence,
+// so each pixel sample can have a stream of its own
+#[derive(Debug, Clone)]
+pub struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+impl Pcg32 {
+    const MULTIPLIER: u64 = 0x5851f42d4c957f2d;
+
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.next();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next();
+        rng
+    }
+
+    fn next(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(Pcg32::MULTIPLIER)
+            .wrapping_add(self.increment);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    // a uniform random number in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        // 53 random bits fill the mantissa exactly
+        let bits = ((self.next() as u64) << 21) ^ (self.next() as u64);
+        (bits & ((1 << 53) - 1)) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// lets scene generators use the helpers of rand::Rng (gen_range etc.)
+impl rand::RngCore for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        self.next()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        ((self.next() as u64) << 32) | self.next() as u64
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+thread_local! {
+    static THREAD_RNG: RefCell<Pcg32> = RefCell::new(Pcg32::new(0, 0));
+}
+
+// restart the current thread's generator on the given stream. the camera
+// does this for every pixel sample, so that random choices made away from
+// the sampler (while intersecting) still come out the same on every run
+pub fn seed_thread_rng(seed: u64, stream: u64) {
+    THREAD_RNG.with(|rng| *rng.borrow_mut() = Pcg32::new(seed, stream));
+}
+
+// a uniform random number in [0, 1) from the current thread's generator
+pub fn random() -> f64 {
+    THREAD_RNG.with(|rng| rng.borrow_mut().next_f64())
+}
+
+// a sampler drawing from the current thread's generator, for the retries
+// of rejection sampling. those can take any number of values, so drawing
+// them from the real sampler would run into the dimensions of the next
+// decision
+pub struct ThreadSampler;
+
+impl Sampler for ThreadSampler {
+    fn start_pixel_sample(&mut self, _pixel: (i32, i32), _index: u32) {}
+
+    fn set_dimension(&mut self, _dimension: u32) {}
+
+    fn get_1d(&mut self) -> f64 {
+        random()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (random(), random())
+    }
+}
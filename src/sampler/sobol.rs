@@ -0,0 +1,95 @@
+use super::{hash, permutation_element, Sampler, ONE_MINUS_EPSILON};
+
+// the first two dimensions of the Sobol sequence, which are well spread
+// in 2d, used for every pair of dimensions with independent Owen
+// scrambling per pair (a "padded" Sobol sampler). this needs no tables of
+// direction numbers for higher dimensions, which converge worse anyway
+pub struct SobolSampler {
+    samples_per_pixel: u32,
+    seed: u64,
+    pixel: (i32, i32),
+    index: u32,
+    dimension: u32,
+}
+
+impl SobolSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.max(1),
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    // the sample index shuffled per dimension, so that dimension pairs
+    // don't line up with each other
+    fn shuffled_index(&self, seed: u64) -> u32 {
+        let wrapped = self.index % self.samples_per_pixel;
+        let index = permutation_element(wrapped, self.samples_per_pixel, seed as u32);
+        self.index - wrapped + index
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), index: u32) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn set_dimension(&mut self, dimension: u32) {
+        self.dimension = dimension;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let seed = hash(self.seed, self.pixel, self.dimension);
+        self.dimension += 1;
+        let index = self.shuffled_index(seed);
+        to_unit(owen_scramble(sobol(index, 0), (seed >> 32) as u32))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let seed = hash(self.seed, self.pixel, self.dimension);
+        self.dimension += 2;
+        let index = self.shuffled_index(seed);
+        (
+            to_unit(owen_scramble(sobol(index, 0), (seed >> 32) as u32)),
+            to_unit(owen_scramble(sobol(index, 1), seed as u32 ^ 0x9e3779b9)),
+        )
+    }
+}
+
+// dimension 0 or 1 of the Sobol point with the given index, as a 32-bit
+// fixed point number
+pub(super) fn sobol(mut index: u32, dimension: u32) -> u32 {
+    let mut v: u32 = 1 << 31;
+    let mut result = 0;
+    while index != 0 {
+        if index & 1 == 1 {
+            result ^= v;
+        }
+        index >>= 1;
+        // dimension 0 is the van der Corput sequence (bit reversal), and
+        // dimension 1 uses the primitive polynomial x + 1
+        v = if dimension == 0 { v >> 1 } else { v ^ (v >> 1) };
+    }
+    result
+}
+
+// a fast approximation of Owen scrambling: flips each bit depending on the
+// bits above it (Laine and Karras, as refined by Burley)
+pub(super) fn owen_scramble(v: u32, seed: u32) -> u32 {
+    let mut v = v.reverse_bits();
+    v ^= v.wrapping_mul(0x3d20adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x05526c56);
+    v ^= v.wrapping_mul(0x53a22864);
+    v.reverse_bits()
+}
+
+pub(super) fn to_unit(v: u32) -> f64 {
+    (v as f64 / 4294967296.0).min(ONE_MINUS_EPSILON)
+}
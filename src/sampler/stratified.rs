@@ -0,0 +1,80 @@
+use super::{hash, permutation_element, sample_stream, Pcg32, Sampler, ONE_MINUS_EPSILON};
+
+// splits each dimension (or pair of dimensions) into one stratum per
+// sample and jitters within it. the strata are visited in a different
+// random order for every pixel and dimension, so that the dimensions of
+// a sample aren't correlated with each other
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    // strata along x and y for 2d samples, with x * y = samples_per_pixel
+    x_strata: u32,
+    y_strata: u32,
+    seed: u64,
+    // jitters the samples within their strata
+    rng: Pcg32,
+    pixel: (i32, i32),
+    index: u32,
+    dimension: u32,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        // the most square grid that uses up every sample
+        let x_strata = (1..=samples_per_pixel)
+            .take_while(|x| x * x <= samples_per_pixel)
+            .filter(|x| samples_per_pixel.is_multiple_of(*x))
+            .last()
+            .unwrap_or(1);
+
+        Self {
+            samples_per_pixel,
+            x_strata,
+            y_strata: samples_per_pixel / x_strata,
+            seed,
+            rng: Pcg32::new(seed, 0),
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn stratum(&self) -> u32 {
+        let seed = hash(self.seed, self.pixel, self.dimension) as u32;
+        permutation_element(
+            self.index % self.samples_per_pixel,
+            self.samples_per_pixel,
+            seed,
+        )
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), index: u32) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+        self.rng = Pcg32::new(self.seed, sample_stream(pixel, index));
+    }
+
+    fn set_dimension(&mut self, dimension: u32) {
+        self.dimension = dimension;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let stratum = self.stratum();
+        self.dimension += 1;
+        ((stratum as f64 + self.rng.next_f64()) / self.samples_per_pixel as f64)
+            .min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let stratum = self.stratum();
+        self.dimension += 2;
+        let (x, y) = (stratum % self.x_strata, stratum / self.x_strata);
+        (
+            ((x as f64 + self.rng.next_f64()) / self.x_strata as f64).min(ONE_MINUS_EPSILON),
+            ((y as f64 + self.rng.next_f64()) / self.y_strata as f64).min(ONE_MINUS_EPSILON),
+        )
+    }
+}
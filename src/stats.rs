@@ -0,0 +1,291 @@
+use std::cell::Cell;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::ops::AddAssign;
+use std::time::Duration;
+
+// counts of the work done while rendering. each thread counts into its own
+// copy, which the renderer takes after every tile, so counting never makes
+// threads wait for each other
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Counters {
+    pub samples: u64,
+    pub camera_rays: u64,
+    // rays scattered off surfaces after the camera ray
+    pub secondary_rays: u64,
+    // rays towards lights. the renderer has no light sampling, so there
+    // are none yet
+    pub shadow_rays: u64,
+    // tests of a ray against a single primitive
+    pub intersection_tests: u64,
+}
+
+impl Counters {
+    pub fn write(&self, mut out: impl Write) -> io::Result<()> {
+        for value in self.values() {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn read(mut input: impl Read) -> io::Result<Self> {
+        let mut values = [0; 5];
+        for value in values.iter_mut() {
+            let mut bytes = [0; 8];
+            input.read_exact(&mut bytes)?;
+            *value = u64::from_le_bytes(bytes);
+        }
+        let [samples, camera_rays, secondary_rays, shadow_rays, intersection_tests] = values;
+        Ok(Self {
+            samples,
+            camera_rays,
+            secondary_rays,
+            shadow_rays,
+            intersection_tests,
+        })
+    }
+
+    fn values(&self) -> [u64; 5] {
+        [
+            self.samples,
+            self.camera_rays,
+            self.secondary_rays,
+            self.shadow_rays,
+            self.intersection_tests,
+        ]
+    }
+}
+
+impl AddAssign for Counters {
+    fn add_assign(&mut self, other: Self) {
+        self.samples += other.samples;
+        self.camera_rays += other.camera_rays;
+        self.secondary_rays += other.secondary_rays;
+        self.shadow_rays += other.shadow_rays;
+        self.intersection_tests += other.intersection_tests;
+    }
+}
+
+thread_local! {
+    static COUNTERS: Cell<Counters> = Cell::new(Counters::default());
+}
+
+// add to the counters of this thread
+pub fn count(update: impl FnOnce(&mut Counters)) {
+    COUNTERS.with(|counters| {
+        let mut value = counters.get();
+        update(&mut value);
+        counters.set(value);
+    });
+}
+
+// the counts of this thread since they were last taken
+pub fn take_counters() -> Counters {
+    COUNTERS.with(|counters| counters.take())
+}
+
+// the report printed at the end of a render
+#[derive(Debug, Clone, Default)]
+pub struct RenderStats {
+    pub counters: Counters,
+    // wall time of each phase, in order
+    pub phases: Vec<(&'static str, Duration)>,
+    // time each render thread spent on tiles
+    pub thread_busy: Vec<Duration>,
+}
+
+impl RenderStats {
+    // add the work of another part of the same render
+    pub fn merge(&mut self, other: RenderStats) {
+        self.counters += other.counters;
+        self.phases.extend(other.phases);
+        if self.thread_busy.len() < other.thread_busy.len() {
+            self.thread_busy
+                .resize(other.thread_busy.len(), Duration::ZERO);
+        }
+        for (busy, other) in self.thread_busy.iter_mut().zip(other.thread_busy) {
+            *busy += other;
+        }
+    }
+
+    fn phase(&self, name: &str) -> Duration {
+        self.phases
+            .iter()
+            .filter(|(phase, _)| *phase == name)
+            .map(|(_, time)| *time)
+            .sum()
+    }
+
+    fn rays(&self) -> u64 {
+        self.counters.camera_rays + self.counters.secondary_rays + self.counters.shadow_rays
+    }
+
+    fn rays_per_second(&self) -> f64 {
+        self.rays() as f64 / self.phase("render").as_secs_f64().max(f64::EPSILON)
+    }
+
+    // segments per path, counting the camera ray
+    fn average_path_length(&self) -> f64 {
+        let paths = self.counters.camera_rays.max(1) as f64;
+        (self.counters.camera_rays + self.counters.secondary_rays) as f64 / paths
+    }
+
+    fn intersection_tests_per_ray(&self) -> f64 {
+        self.counters.intersection_tests as f64 / self.rays().max(1) as f64
+    }
+
+    // the share of the render time each thread was busy
+    fn thread_utilization(&self) -> Vec<f64> {
+        let render = self.phase("render").as_secs_f64().max(f64::EPSILON);
+        self.thread_busy
+            .iter()
+            .map(|busy| (busy.as_secs_f64() / render).min(1.0))
+            .collect()
+    }
+
+    pub fn to_json(&self) -> String {
+        let phases: Vec<String> = self
+            .phases
+            .iter()
+            .map(|(phase, time)| format!("\"{}\": {}", phase, time.as_secs_f64()))
+            .collect();
+        let utilization: Vec<String> = self
+            .thread_utilization()
+            .iter()
+            .map(|share| share.to_string())
+            .collect();
+        format!(
+            "{{\n  \"samples\": {},\n  \"camera_rays\": {},\n  \"secondary_rays\": {},\n  \
+             \"shadow_rays\": {},\n  \"rays_per_second\": {},\n  \"average_path_length\": {},\n  \
+             \"intersection_tests\": {},\n  \"intersection_tests_per_ray\": {},\n  \
+             \"bvh_node_visits\": null,\n  \"phase_seconds\": {{{}}},\n  \
+             \"thread_utilization\": [{}]\n}}\n",
+            self.counters.samples,
+            self.counters.camera_rays,
+            self.counters.secondary_rays,
+            self.counters.shadow_rays,
+            self.rays_per_second(),
+            self.average_path_length(),
+            self.counters.intersection_tests,
+            self.intersection_tests_per_ray(),
+            phases.join(", "),
+            utilization.join(", "),
+        )
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Render statistics")?;
+        writeln!(f, "  samples                     {}", self.counters.samples)?;
+        writeln!(
+            f,
+            "  camera rays                 {}",
+            self.counters.camera_rays
+        )?;
+        writeln!(
+            f,
+            "  secondary rays              {}",
+            self.counters.secondary_rays
+        )?;
+        writeln!(
+            f,
+            "  shadow rays                 {}",
+            self.counters.shadow_rays
+        )?;
+        writeln!(
+            f,
+            "  rays per second             {:.0}",
+            self.rays_per_second()
+        )?;
+        writeln!(
+            f,
+            "  average path length         {:.2}",
+            self.average_path_length()
+        )?;
+        writeln!(
+            f,
+            "  intersection tests per ray  {:.2}",
+            self.intersection_tests_per_ray()
+        )?;
+        // objects are tested one by one from a list
+        writeln!(f, "  BVH node visits             none (no BVH)")?;
+        for (phase, time) in &self.phases {
+            writeln!(
+                f,
+                "  {:<28}{:.3} s",
+                format!("{} time", phase),
+                time.as_secs_f64()
+            )?;
+        }
+        let utilization: Vec<String> = self
+            .thread_utilization()
+            .iter()
+            .map(|share| format!("{:.0}%", 100.0 * share))
+            .collect();
+        if !utilization.is_empty() {
+            writeln!(f, "  thread utilization          {}", utilization.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(samples: u64, phases: Vec<(&'static str, Duration)>, busy: &[u64]) -> RenderStats {
+        RenderStats {
+            counters: Counters {
+                samples,
+                camera_rays: samples,
+                secondary_rays: 2 * samples,
+                shadow_rays: 0,
+                intersection_tests: 12 * samples,
+            },
+            phases,
+            thread_busy: busy.iter().map(|&secs| Duration::from_secs(secs)).collect(),
+        }
+    }
+
+    #[test]
+    fn merge_adds_counters_phases_and_busy_time() {
+        let mut first = stats(10, vec![("render", Duration::from_secs(2))], &[1, 2]);
+        first.merge(stats(
+            5,
+            vec![("render", Duration::from_secs(4))],
+            &[3, 1, 4],
+        ));
+        assert_eq!(first.counters.samples, 15);
+        assert_eq!(first.counters.secondary_rays, 30);
+        assert_eq!(first.phase("render"), Duration::from_secs(6));
+        let busy: Vec<u64> = first.thread_busy.iter().map(Duration::as_secs).collect();
+        assert_eq!(busy, [4, 3, 4]);
+        assert_eq!(first.thread_utilization(), [4.0 / 6.0, 0.5, 4.0 / 6.0]);
+    }
+
+    #[test]
+    fn json_has_every_statistic() {
+        let phases = vec![
+            ("setup", Duration::from_millis(500)),
+            ("render", Duration::from_secs(3)),
+            ("output", Duration::from_millis(250)),
+        ];
+        let json: serde_json::Value =
+            serde_json::from_str(&stats(100, phases, &[3, 0]).to_json()).unwrap();
+        assert_eq!(json["samples"], 100);
+        assert_eq!(json["camera_rays"], 100);
+        assert_eq!(json["secondary_rays"], 200);
+        assert_eq!(json["shadow_rays"], 0);
+        assert_eq!(json["rays_per_second"], 100.0);
+        assert_eq!(json["average_path_length"], 3.0);
+        assert_eq!(json["intersection_tests"], 1200);
+        assert_eq!(json["intersection_tests_per_ray"], 4.0);
+        assert!(json["bvh_node_visits"].is_null());
+        assert_eq!(json["phase_seconds"]["setup"], 0.5);
+        assert_eq!(json["phase_seconds"]["render"], 3.0);
+        assert_eq!(json["phase_seconds"]["output"], 0.25);
+        let utilization = json["thread_utilization"].as_array().unwrap();
+        assert_eq!(utilization, &[1.0, 0.0]);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::color::Color;
+use crate::vec3::Point;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub trait Texture: Send + Sync {
+    // return the color of the texture at surface coordinates (u, v),
+    // which lie at point p in world space
+    fn value(&self, u: f64, v: f64, p: Point) -> Color;
+}
+
+// a texture with the same color everywhere
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: Point) -> Color {
+        self.albedo
+    }
+}
+
+// a 3D checkerboard that alternates between two textures, with cells of
+// side length `scale` in world space
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Box<dyn Texture>,
+    odd: Box<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Color, odd: Color) -> Self {
+        Self {
+            inv_scale: 1.0 / scale,
+            even: Box::new(SolidColor::new(even)),
+            odd: Box::new(SolidColor::new(odd)),
+        }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: Point) -> Color {
+        let x = (self.inv_scale * p.x).floor() as i64;
+        let y = (self.inv_scale * p.y).floor() as i64;
+        let z = (self.inv_scale * p.z).floor() as i64;
+
+        if (x + y + z) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// a texture backed by a PPM/PGM image (ASCII P2/P3 or binary P5/P6),
+// bilinearly filtered and repeated outside of [0, 1]^2. values are returned
+// as stored in [0, 1] without any gamma conversion, which is what normal,
+// height and mask maps expect
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        // the header is four whitespace separated tokens (with optional
+        // comments), followed by a single whitespace byte for binary formats
+        let mut pos = 0;
+        let mut header = Vec::new();
+        while header.len() < 4 {
+            while pos < data.len() && (data[pos].is_ascii_whitespace() || data[pos] == b'#') {
+                if data[pos] == b'#' {
+                    while pos < data.len() && data[pos] != b'\n' {
+                        pos += 1;
+                    }
+                } else {
+                    pos += 1;
+                }
+            }
+            let start = pos;
+            while pos < data.len() && !data[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if start == pos {
+                return Err(invalid("truncated image header"));
+            }
+            header.push(String::from_utf8_lossy(&data[start..pos]).into_owned());
+        }
+        pos += 1;
+
+        let (channels, binary) = match header[0].as_str() {
+            "P2" => (1, false),
+            "P3" => (3, false),
+            "P5" => (1, true),
+            "P6" => (3, true),
+            _ => return Err(invalid("unsupported image format")),
+        };
+        let parse = |s: &str| s.parse::<usize>().map_err(|_| invalid("bad image header"));
+        let width = parse(&header[1])?;
+        let height = parse(&header[2])?;
+        let max_value = parse(&header[3])?;
+        if max_value == 0 || max_value > 65535 {
+            return Err(invalid("bad image max value"));
+        }
+
+        let count = width * height * channels;
+        let samples: Vec<usize> = if binary {
+            let bytes_per_sample = if max_value < 256 { 1 } else { 2 };
+            let body = data.get(pos..).unwrap_or(&[]);
+            if body.len() < count * bytes_per_sample {
+                return Err(invalid("truncated image data"));
+            }
+            body.chunks(bytes_per_sample)
+                .take(count)
+                .map(|c| c.iter().fold(0, |acc, &b| (acc << 8) | b as usize))
+                .collect()
+        } else {
+            let body = String::from_utf8_lossy(data.get(pos..).unwrap_or(&[])).into_owned();
+            let samples = body
+                .split_ascii_whitespace()
+                .take(count)
+                .map(parse)
+                .collect::<io::Result<Vec<_>>>()?;
+            if samples.len() < count {
+                return Err(invalid("truncated image data"));
+            }
+            samples
+        };
+
+        let scale = 1.0 / max_value as f64;
+        let pixels = samples
+            .chunks(channels)
+            .map(|c| {
+                if channels == 1 {
+                    let x = c[0] as f64 * scale;
+                    Color::new(x, x, x)
+                } else {
+                    Color::new(
+                        c[0] as f64 * scale,
+                        c[1] as f64 * scale,
+                        c[2] as f64 * scale,
+                    )
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    fn pixel(&self, i: i64, j: i64) -> Color {
+        let i = i.rem_euclid(self.width as i64) as usize;
+        let j = j.rem_euclid(self.height as i64) as usize;
+        self.pixels[j * self.width + i]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Point) -> Color {
+        if self.pixels.is_empty() {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        // v = 0 is the bottom row of the image; shift by half a pixel so
+        // that texel centers land on integer coordinates
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        let (i, j) = (x.floor() as i64, y.floor() as i64);
+        let (fx, fy) = (x - x.floor(), y - y.floor());
+
+        (1.0 - fx) * (1.0 - fy) * self.pixel(i, j)
+            + fx * (1.0 - fy) * self.pixel(i + 1, j)
+            + (1.0 - fx) * fy * self.pixel(i, j + 1)
+            + fx * fy * self.pixel(i + 1, j + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // write the bytes to a temporary file and load them as a texture
+    fn load(name: &str, bytes: &[u8]) -> io::Result<ImageTexture> {
+        let path =
+            std::env::temp_dir().join(format!("texture-test-{}-{}", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        let texture = ImageTexture::load(&path);
+        fs::remove_file(&path).unwrap();
+        texture
+    }
+
+    #[test]
+    fn loads_ascii_and_binary_images() {
+        let gray = [
+            Color::new(0.0, 0.0, 0.0),
+            Color::new(0.2, 0.2, 0.2),
+            Color::new(1.0, 1.0, 1.0),
+        ];
+        for (name, bytes) in [
+            ("p2", b"P2 3 1 5\n0 1 5\n".to_vec()),
+            ("p5", [b"P5 3 1 5\n".to_vec(), vec![0, 1, 5]].concat()),
+        ] {
+            let texture = load(name, &bytes).unwrap();
+            assert_eq!((texture.width, texture.height), (3, 1));
+            assert_eq!(texture.pixels, gray);
+        }
+
+        let color = [Color::new(1.0, 0.0, 0.5), Color::new(0.0, 0.25, 0.0)];
+        for (name, bytes) in [
+            ("p3", b"P3 1 2 4\n4 0 2\n0 1 0\n".to_vec()),
+            (
+                "p6",
+                [b"P6 1 2 4\n".to_vec(), vec![4, 0, 2, 0, 1, 0]].concat(),
+            ),
+        ] {
+            let texture = load(name, &bytes).unwrap();
+            assert_eq!((texture.width, texture.height), (1, 2));
+            assert_eq!(texture.pixels, color);
+        }
+    }
+
+    #[test]
+    fn skips_header_comments() {
+        let bytes = b"P2\n# made by hand\n2 # width\n1\n# max value next\n10\n10 5\n";
+        let texture = load("comments", bytes).unwrap();
+        assert_eq!(
+            texture.pixels,
+            [Color::new(1.0, 1.0, 1.0), Color::new(0.5, 0.5, 0.5)]
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_and_unsupported_images() {
+        for (name, bytes) in [
+            ("header", b"P3 2 2".to_vec()),
+            ("ascii", b"P3 1 1 255\n255 0\n".to_vec()),
+            (
+                "binary",
+                [b"P6 2 1 255\n".to_vec(), vec![1, 2, 3, 4]].concat(),
+            ),
+            ("wide", [b"P5 2 1 1000\n".to_vec(), vec![0, 1, 2]].concat()),
+            ("format", b"P1 1 1\n1\n".to_vec()),
+        ] {
+            let err = load(name, &bytes).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", name);
+        }
+    }
+}
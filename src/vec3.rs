@@ -24,7 +24,7 @@ impl Vec3 {
     }
 
     pub fn dot(v: Vec3, u: Vec3) -> f64 {
-        return v.x * u.x + v.y * u.y + v.z * u.z;
+        v.x * u.x + v.y * u.y + v.z * u.z
     }
 
     pub fn cross(v: Vec3, u: Vec3) -> Vec3 {
@@ -35,25 +35,15 @@ impl Vec3 {
         )
     }
 
-    pub fn random() -> Vec3 {
+    pub fn random(rng: &mut impl Rng) -> Vec3 {
         Vec3 {
-            x: rand::random(),
-            y: rand::random(),
-            z: rand::random(),
+            x: rng.gen(),
+            y: rng.gen(),
+            z: rng.gen(),
         }
     }
 
-    pub fn random_range(min: f64, max: f64) -> Vec3 {
-        let mut rng = rand::thread_rng();
-        Vec3 {
-            x: rng.gen_range(min..=max),
-            y: rng.gen_range(min..=max),
-            z: rng.gen_range(min..=max),
-        }
-    }
-
-    pub fn random_disk(min: f64, max: f64) -> Vec3 {
-        let mut rng = rand::thread_rng();
+    pub fn random_range(rng: &mut impl Rng, min: f64, max: f64) -> Vec3 {
         Vec3 {
             x: rng.gen_range(min..=max),
             y: rng.gen_range(min..=max),
@@ -175,40 +165,30 @@ pub fn refract(direction: Vec3, normal: Vec3, refraction_ratio: f64) -> Vec3 {
 }
 
 
-// note: we first sample points from within the sphere and then
-// normalize them. We do this to avoid oversampling from points
-// toward the "corners" of the sphere (~ 45 degrees).
-fn random_in_unit_sphere() -> Vec3 {
-    loop {
-        let p = Vec3::random_range(-1.0, 1.0);
-        if p.length() < 1.0 {
-            return p;
-        }
-    }
-}
-
-pub fn random_unit_vector() -> Vec3 {
-    random_in_unit_sphere().unit()
+// a uniformly distributed unit vector for a point u in [0, 1)^2
+pub fn sample_unit_vector(u: (f64, f64)) -> Vec3 {
+    let z = 1.0 - 2.0 * u.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * std::f64::consts::PI * u.1;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
 }
 
-pub fn random_in_unit_disk() -> Vec3 {
-    let mut rng = rand::thread_rng();
-    loop {
-        let p = Vec3::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0), 0.0);
-        if p.length() < 1.0 {
-            return p;
-        }
+// a uniformly distributed point in the unit disk for a point u in [0, 1)^2.
+// this maps concentric squares to concentric circles (Shirley and Chiu),
+// so that nearby points in the square stay nearby on the disk
+pub fn sample_unit_disk(u: (f64, f64)) -> Vec3 {
+    let (x, y) = (2.0 * u.0 - 1.0, 2.0 * u.1 - 1.0);
+    if x == 0.0 && y == 0.0 {
+        return Vec3::new(0.0, 0.0, 0.0);
     }
-}
 
-#[allow(dead_code)]
-pub fn random_on_hemisphere(normal: Vec3) -> Vec3 {
-    let on_unit_sphere = random_unit_vector();
-    if Vec3::dot(on_unit_sphere, normal) > 0.0 {
-        on_unit_sphere
+    let quarter_pi = std::f64::consts::FRAC_PI_4;
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, quarter_pi * (y / x))
     } else {
-        -on_unit_sphere
-    }
+        (y, 2.0 * quarter_pi - quarter_pi * (x / y))
+    };
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
 }
 
 #[cfg(test)]